@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS idx_mods_author_id;
+
+DROP TABLE IF EXISTS authors;
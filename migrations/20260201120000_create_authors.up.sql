@@ -0,0 +1,13 @@
+-- Steam profile data of mod authors, refreshed by the scheduled sync
+CREATE TABLE authors (
+    author_id       BIGINT PRIMARY KEY,
+    persona_name    TEXT NOT NULL,
+    profile_url     TEXT NOT NULL,
+    avatar          TEXT NOT NULL,
+    avatar_medium   TEXT NOT NULL,
+    avatar_full     TEXT NOT NULL,
+    country_code    TEXT,
+    updated_at      TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_mods_author_id ON mods (author_id);
@@ -0,0 +1,13 @@
+-- Every display name an author has used in the kvtag 'Author' field
+CREATE TABLE author_names (
+    author_id       BIGINT NOT NULL,
+    name            TEXT NOT NULL,
+    first_seen      DATE NOT NULL,
+    last_seen       DATE NOT NULL,
+
+    PRIMARY KEY (author_id, name)
+);
+
+INSERT INTO author_names (author_id, name, first_seen, last_seen)
+SELECT DISTINCT author_id, author, CURRENT_DATE, CURRENT_DATE
+FROM mods;
@@ -0,0 +1,9 @@
+-- Resolved steam vanity names and mod internal names, id is NULL if the name couldn't be resolved
+CREATE TABLE name_cache (
+    kind            TEXT NOT NULL,
+    name            TEXT NOT NULL,
+    id              BIGINT,
+    resolved_at     TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+
+    PRIMARY KEY (kind, name)
+);
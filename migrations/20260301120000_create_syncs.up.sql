@@ -0,0 +1,5 @@
+-- When a table was last synced with steam, used for the Last-Modified header
+CREATE TABLE syncs (
+    name            TEXT PRIMARY KEY,
+    synced_at       TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,4 @@
+DROP TABLE IF EXISTS legacy_authors;
+DROP TABLE IF EXISTS legacy_mod_history;
+DROP TABLE IF EXISTS legacy_mods;
+DROP TABLE IF EXISTS legacy_mod_list;
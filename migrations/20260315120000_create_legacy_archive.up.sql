@@ -0,0 +1,57 @@
+-- Archive of the 1.3 api, served when javid.ddns.net can't be scraped
+
+-- modmigrationprogressalltime.php merged with modmigrationprogress.php, replaced on every archive run
+CREATE TABLE legacy_mod_list (
+    rank                INTEGER PRIMARY KEY,
+    display_name        TEXT NOT NULL,
+    -- NULL if the mod isn't on modmigrationprogress.php
+    internal_name       TEXT,
+    downloads_total     BIGINT NOT NULL,
+    downloads_today     BIGINT NOT NULL,
+    downloads_yesterday BIGINT NOT NULL,
+    mod_version         TEXT NOT NULL,
+    tmodloader_version  TEXT NOT NULL,
+    archived_at         TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- tools/modinfo.php and moddescription.php
+CREATE TABLE legacy_mods (
+    internal_name       TEXT PRIMARY KEY,
+    display_name        TEXT NOT NULL,
+    version             TEXT NOT NULL,
+    author              TEXT NOT NULL,
+    download_link       TEXT NOT NULL,
+    downloads_total     BIGINT NOT NULL,
+    downloads_yesterday BIGINT NOT NULL,
+    last_updated        TEXT NOT NULL,
+    tmodloader_version  TEXT NOT NULL,
+    modreferences       TEXT NOT NULL,
+    modside             TEXT NOT NULL,
+    description         TEXT,
+    homepage            TEXT,
+    icon                TEXT,
+    archived_at         TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- tools/moddownloadhistory.php, one row per version in the order of the page
+CREATE TABLE legacy_mod_history (
+    internal_name       TEXT NOT NULL,
+    position            INTEGER NOT NULL,
+    version             TEXT NOT NULL,
+    downloads_total     BIGINT NOT NULL,
+    tmodloader_version  TEXT NOT NULL,
+    publish_date        TEXT NOT NULL,
+    archived_at         TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+
+    PRIMARY KEY (internal_name, position)
+);
+
+-- tools/ranksbysteamid.php with the steam profile of the author
+CREATE TABLE legacy_authors (
+    steam_id            BIGINT PRIMARY KEY,
+    steam_name          TEXT NOT NULL,
+    steam_avatar        TEXT NOT NULL,
+    mods                JSONB NOT NULL,
+    maintained_mods     JSONB NOT NULL,
+    archived_at         TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,2 @@
+ALTER TABLE legacy_mod_history
+    ALTER COLUMN publish_date TYPE TEXT USING to_char(publish_date AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS');
@@ -0,0 +1,7 @@
+-- the dates were stored as scraped, rows with anything else in them can't be converted and are dropped,
+-- they're archived again the next time the mod is archived
+DELETE FROM legacy_mod_history
+    WHERE btrim(publish_date) !~ '^\d{4}-\d{2}-\d{2} \d{2}:\d{2}:\d{2}$';
+-- assumed to be in UTC like the other timestamps of the 1.3 server, see TIMESTAMP_FORMAT in src/api13/parse.rs
+ALTER TABLE legacy_mod_history
+    ALTER COLUMN publish_date TYPE TIMESTAMPTZ USING btrim(publish_date)::TIMESTAMP AT TIME ZONE 'UTC';
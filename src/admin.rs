@@ -0,0 +1,78 @@
+use std::sync::Arc;
+use rocket::State;
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::serde::json::serde_json::{self, Value};
+use crate::cache::CacheAdmin;
+
+pub struct AdminState {
+	// the admin endpoints are disabled if no token is configured
+	pub token: Option<String>,
+	pub caches: Vec<Arc<dyn CacheAdmin>>,
+}
+
+impl AdminState {
+	pub fn init(caches: Vec<Arc<dyn CacheAdmin>>) -> AdminState {
+		AdminState {
+			token: std::env::var("ADMIN_TOKEN").ok().filter(|token| !token.is_empty()),
+			caches,
+		}
+	}
+
+	fn cache(&self, name: &str) -> Option<&Arc<dyn CacheAdmin>> {
+		self.caches.iter().find(|cache| cache.name() == name)
+	}
+}
+
+// request guard for the admin endpoints, expects 'Authorization: Bearer <ADMIN_TOKEN>'
+pub struct Admin;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Admin {
+	type Error = ();
+
+	async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+		let Some(token) = req.rocket().state::<AdminState>().and_then(|state| state.token.as_deref()) else {
+			return Outcome::Error((Status::Forbidden, ()));
+		};
+
+		let provided = req.headers().get_one("Authorization").and_then(|header| header.strip_prefix("Bearer "));
+		match provided {
+			Some(provided) if constant_time_eq(provided.as_bytes(), token.as_bytes()) => Outcome::Success(Admin),
+			_ => Outcome::Error((Status::Unauthorized, ()))
+		}
+	}
+}
+
+// compares without returning early, so the token can't be guessed by timing the responses
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+	a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[get("/caches")]
+pub async fn cache_stats(_admin: Admin, state: &State<AdminState>) -> Value {
+	let mut stats = Vec::with_capacity(state.caches.len());
+	for cache in &state.caches {
+		stats.push(cache.stats().await);
+	}
+	serde_json::json!(stats)
+}
+
+#[delete("/caches/<name>")]
+pub async fn clear_cache(_admin: Admin, name: &str, state: &State<AdminState>) -> Option<Value> {
+	let cache = state.cache(name)?;
+	cache.clear().await;
+	Some(serde_json::json!(cache.stats().await))
+}
+
+#[delete("/caches/<name>/<key>")]
+pub async fn invalidate_cache_key(_admin: Admin, name: &str, key: &str, state: &State<AdminState>) -> Option<Value> {
+	let cache = state.cache(name)?;
+	Some(serde_json::json!({
+		"invalidated": cache.invalidate(key).await
+	}))
+}
+
+pub fn get_routes() -> Vec<rocket::Route> {
+	routes![cache_stats, clear_cache, invalidate_cache_key]
+}
@@ -1,30 +1,52 @@
 extern crate reqwest;
 
 use std::collections::HashMap;
-use rocket::State;
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
+use rocket::{Request, State};
+use rocket::form;
+use rocket::http::Header;
+use rocket::response::{self, Responder};
 use rocket::serde::json::serde_json::{self, json, Value};
 use rocket_cache_response::CacheResponse;
-use scraper::{Html, Selector};
-use crate::{APIError, cached_json, steamapi, steamapi::get_user_info};
-use crate::cache;
+use schemars::JsonSchema;
+use scraper::Html;
+use sqlx::PgPool;
+use crate::{APIError, cached_json, stale_json, steamapi, steamapi::get_user_info};
+use crate::api_error::check_upstream;
+use crate::cache::Stale;
 use crate::api13::responses::*;
+use crate::api13::{archive, icons};
+use crate::api14::list_api::{optional, SortOrder, DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE};
+use crate::api13::parse::{self, MIGRATION_PROGRESS, MIGRATION_PROGRESS_ALL_TIME, RANKS_BY_STEAMID, DOWNLOAD_HISTORY};
 
 use super::Api13State;
 
 const API_URL: &str = "http://javid.ddns.net/tModLoader";
 
-async fn get_html(url: &str) -> Result<Html, reqwest::Error> {
-	let res = reqwest::get(url).await?;
+// the server is slow, but without a timeout a dead server would keep requests from falling back to the archive
+static CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| reqwest::Client::builder()
+	.timeout(Duration::from_secs(20))
+	.build()
+	.unwrap_or_default());
+
+async fn get_html(url: &str) -> Result<Html, APIError> {
+	let res = check_upstream(CLIENT.get(url).send().await?)?;
 	let body = res.text().await?;
 	return Ok(Html::parse_document(&body));
 }
 
+async fn scrape_count() -> Result<usize, APIError> {
+	let html = get_html(&format!("{API_URL}/{MIGRATION_PROGRESS}")).await?;
+	Ok(parse::parse_count(&html)?)
+}
+
 #[get("/count")]
-pub async fn count_1_3() -> Result<Value, APIError> {
-	let html = get_html(&format!("{API_URL}/modmigrationprogress.php")).await?;
-	let selector = Selector::parse("table > tbody > tr")?; // get all 'tr' inside 'tbody' and 'table'
-	let selection = html.select(&selector); // generate iterator based on selector
-	let count = selection.skip(1).count(); // count the number of elements except the first one
+pub async fn count_1_3(state: &State<Api13State>) -> Result<Value, APIError> {
+	let count = match scrape_count().await {
+		Ok(count) => count,
+		Err(e) => archive::or_archived(e, archive::load_count(&state.db).await, "1.3 mod count")?
+	};
 
 	// return formatted json response
 	Ok(json!({
@@ -32,215 +54,254 @@ pub async fn count_1_3() -> Result<Value, APIError> {
 	}))
 }
 
-#[get("/mod/<modname>")]
-pub async fn mod_1_3(modname: &str, state: &State<Api13State>) -> Result<CacheResponse<Value>, APIError> {
-	let mod_info = match cache::lock_and_get(&state.mod_cache, modname.to_owned(), 3600) {
-		Some(cached_value) => cached_value,
-		None => {
-			let client = reqwest::Client::new();
-
-			// get mod info
-			let res = client.get(&format!("{API_URL}/tools/modinfo.php?modname={}", modname)).send().await?;
-			let mut modinfo: ModInfo = res.json::<ModInfo>().await.map_err(|_| {
-				APIError::InvalidModName(modname.to_owned())
-			})?;
-
-			// get description response; save info in DescriptionResponse struct
-			let description: DescriptionResponse = client 
-				.post(format!("{API_URL}/moddescription.php"))
-				.form(&HashMap::from([("modname", &modname)]))
-				.send().await?
-				.json().await?;
-
-			modinfo.description = Some(description.description);
-			modinfo.homepage = Some(description.homepage);
-
-			// get icon url if it exists
-			let icon_url = format!("{API_URL}/modicons/modiconuploads/{}_{}.png", modname, modinfo.version);
-			let res = client.get(&icon_url).send().await;
-			modinfo.icon = match res {
-				Ok(_) => Some(icon_url),
-				Err(_) => None
-			};
-
-			cache::lock_and_update(&state.mod_cache, modname.to_owned(), modinfo)
+pub(super) async fn scrape_mod_info(modname: &str) -> Result<ModInfo, APIError> {
+	// get mod info
+	let res = CLIENT.get(format!("{API_URL}/tools/modinfo.php?modname={}", modname)).send().await?;
+	let body = check_upstream(res)?.text().await?;
+	let mut modinfo = parse::parse_mod_info(&body)
+		.map_err(|e| {
+			log::warn!("Could not parse the mod info of '{modname}': {e}");
+			APIError::JSONError(e.to_string())
+		})?
+		.ok_or_else(|| APIError::ModNameNotFound(modname.to_owned()))?;
+
+	// get description response; save info in DescriptionResponse struct
+	let res = CLIENT
+		.post(format!("{API_URL}/moddescription.php"))
+		.form(&HashMap::from([("modname", modname)]))
+		.send().await?;
+	let description: DescriptionResponse = check_upstream(res)?.json().await?;
+
+	modinfo.description = Some(description.description);
+	modinfo.homepage = Some(description.homepage);
+
+	// get icon url if it exists
+	let icon_url = format!("{API_URL}/modicons/modiconuploads/{}_{}.png", modname, modinfo.version);
+	modinfo.icon = icons::icon_url(&CLIENT, icon_url, modname, &modinfo.version).await;
+
+	Ok(modinfo)
+}
+
+async fn fetch_mod_info(modname: String, db: Arc<PgPool>) -> Result<ModInfo, APIError> {
+	match scrape_mod_info(&modname).await {
+		Ok(info) => {
+			archive::log_failure(archive::save_mod_info(&info, &db).await, &format!("1.3 mod '{modname}'"));
+			Ok(info)
 		}
-	};
+		Err(e) => archive::or_archived(e, archive::load_mod_info(&modname, &db).await, &format!("1.3 mod '{modname}'"))
+	}
+}
+
+// also returns for how long the data has been expired
+pub async fn get_mod_info(modname: &str, state: &Api13State) -> Result<(ModInfo, Option<Duration>), APIError> {
+	let fetch_name = modname.to_owned();
+	let db = Arc::clone(&state.db);
+	state.mod_cache
+		.get_or_revalidate_with(modname.to_owned(), move || fetch_mod_info(fetch_name, db))
+		.await
+}
+
+#[get("/mod/<modname>")]
+pub async fn mod_1_3(modname: &str, state: &State<Api13State>) -> Result<Stale<CacheResponse<Value>>, APIError> {
+	let (mod_info, stale_for) = get_mod_info(modname, state).await?;
 
-	return cached_json!(mod_info, 3600, false);
+	stale_json!(mod_info, stale_for, 3600, false)
 }
 
 #[get("/author/<steamid>", rank=1)]
-pub async fn author_1_3(steamid: u64, state: &State<Api13State>) -> Result<CacheResponse<Value>, APIError> {
+pub async fn author_1_3(steamid: u64, state: &State<Api13State>) -> Result<Stale<CacheResponse<Value>>, APIError> {
 	return get_author_info(steamapi::validate_steamid64(steamid)?, state).await;
 }
 
 #[get("/author/<steamname>", rank=2)]
-pub async fn author_1_3_str(steamname: &str, state: &State<Api13State>) -> Result<CacheResponse<Value>, APIError> {
-	let steamid = steamapi::steamname_to_steamid(steamname, &state.steam_api_key).await?;
+pub async fn author_1_3_str(steamname: &str, state: &State<Api13State>) -> Result<Stale<CacheResponse<Value>>, APIError> {
+	let steamid = state.name_cache.steam_id(steamname, &state.steam_api_key).await?;
 	return get_author_info(steamid, state).await;
 }
 
-async fn get_author_info(steamid: u64, state: &State<Api13State>) -> Result<CacheResponse<Value>, APIError> {
-	let author = match cache::lock_and_get(&state.author_cache, steamid, 3600) {
-		Some(cached_value) => cached_value,
-		None => {
-			let steam_user = get_user_info(steamid, &state.steam_api_key).await?;
+async fn get_author_info(steamid: u64, state: &State<Api13State>) -> Result<Stale<CacheResponse<Value>>, APIError> {
+	let steam_api_key = Arc::clone(&state.steam_api_key);
+	let db = Arc::clone(&state.db);
+	let (author, stale_for) = state.author_cache
+		.get_or_revalidate_with(steamid, move || fetch_author_info(steamid, steam_api_key, db))
+		.await?;
 
-			let td_selector = &Selector::parse("td")?;
+	stale_json!(author, stale_for, 3600, false)
+}
 
-			let html = get_html(&format!("{API_URL}/tools/ranksbysteamid.php?steamid64={}", steamid)).await?;
-			let table_selector = Selector::parse("table > tbody")?;
-			let mut tables = html.select(&table_selector); // there are 4 tables
+pub(super) fn author_info(steamid: u64, steam_name: String, steam_avatar: String, mods: Vec<AuthorModInfo>, maintained_mods: Vec<MaintainedModInfo>) -> AuthorInfo {
+	AuthorInfo {
+		steam_id: steamid.to_string(),
+		steam_name,
+		steam_avatar,
+		downloads_total: mods.iter().map(|m| m.downloads_total).sum(),
+		downloads_yesterday: mods.iter().map(|m| m.downloads_yesterday).sum(),
+		total: mods.len() as u32,
+		mods,
+		maintained_mods
+	}
+}
 
-			let first_table = tables.next().unwrap();
-			let mod_selector = &Selector::parse("tr:not(:first-child)")?;
-			let mods_data = first_table.select(mod_selector);
-			let mut mods: Vec<AuthorModInfo> = Vec::new();
+pub(super) async fn scrape_author_info(steamid: u64, steam_api_key: &str) -> Result<AuthorInfo, APIError> {
+	let steam_user = get_user_info(steamid, steam_api_key).await?;
 
-			let mut total_downloads: u32 = 0;
-			let mut total_downloads_yesterday: u32 = 0;
+	let html = get_html(&format!("{API_URL}/{RANKS_BY_STEAMID}?steamid64={}", steamid)).await?;
 
-			for mod_item in mods_data {
-				let mut children = mod_item.select(td_selector);
+	let (mods, maintained_mods) = parse::parse_author_mods(&html)?;
 
-				// add mod info to mod list
-				// a lot of unwraps because I trust that there is no garbage
-				let rank = children.next().unwrap().inner_html().parse().unwrap();
-				let display_name = children.next().unwrap().inner_html();
-				let downloads_total = children.next().unwrap().inner_html().parse().unwrap();
-				let downloads_yesterday = children.next().unwrap().inner_html().parse().unwrap();
+	Ok(author_info(steamid, steam_user.personaname, steam_user.avatarfull, mods, maintained_mods))
+}
 
-				// increment totals
-				total_downloads += downloads_total;
-				total_downloads_yesterday += downloads_yesterday;
+async fn fetch_author_info(steamid: u64, steam_api_key: Arc<String>, db: Arc<PgPool>) -> Result<AuthorInfo, APIError> {
+	match scrape_author_info(steamid, &steam_api_key).await {
+		Ok(author) => {
+			archive::log_failure(archive::save_author(&author, &db).await, &format!("1.3 author {steamid}"));
+			Ok(author)
+		}
+		Err(e) => archive::or_archived(e, archive::load_author(steamid, &db).await, &format!("1.3 author {steamid}"))
+	}
+}
 
-				mods.push(AuthorModInfo { rank, display_name, downloads_total, downloads_yesterday });
-			}
+pub(super) async fn scrape_mod_list(db: &PgPool) -> Result<Vec<ModListInfo>, APIError> {
+	// new scopes because Html can't be held across an await
+	let mut mods = {
+		let html = get_html(&format!("{API_URL}/{MIGRATION_PROGRESS_ALL_TIME}")).await?;
+		parse::parse_mod_list(&html)?
+	};
 
-			let maintainer_table = tables.last().unwrap();
-			let maintained_mods_selector = Selector::parse("tr:not(:first-child)")?;
-			let maintained_mods = maintainer_table.select(&maintained_mods_selector);
+	// the missing fields are on the page with today's downloads
+	let today = {
+		let html = get_html(&format!("{API_URL}/{MIGRATION_PROGRESS}")).await?;
+		parse::parse_todays_downloads(&html)?
+	};
 
-			let mut maintained_mods_infos: Vec<MaintainedModInfo> = Vec::new();
+	let unmatched = parse::merge_mod_list(&mut mods, today);
+	if !unmatched.is_empty() {
+		let names: Vec<&str> = unmatched.iter().map(|row| row.internal_name.as_str()).collect();
+		log::warn!("{} mods on {MIGRATION_PROGRESS} are missing on {MIGRATION_PROGRESS_ALL_TIME}: {}", names.len(), names.join(", "));
+	}
 
-			for maintained_mod in maintained_mods {
-				let mut children = maintained_mod.select(td_selector);
+	match archive::archived_names(db).await {
+		Ok(archived) => parse::fill_archived_names(&mut mods, &archived),
+		Err(e) => log::warn!("Could not read the archived 1.3 mod names: {e}")
+	}
 
-				maintained_mods_infos.push(MaintainedModInfo {
-					internal_name: children.next().unwrap().inner_html(),
-					downloads_total: children.next().unwrap().inner_html().parse().unwrap(),
-					downloads_yesterday: children.next().unwrap().inner_html().parse().unwrap()
-				})
-			}
+	let unknown = mods.iter().filter(|m| m.merge_status == MergeStatus::Unmatched).count();
+	if unknown > 0 {
+		log::info!("The internal name of {unknown} 1.3 mods is unknown");
+	}
 
-			let author = AuthorInfo {
-				steam_id: steamid.to_string(),
-				steam_name: steam_user.personaname,
-				steam_avatar: steam_user.avatarfull,
-				downloads_total: total_downloads,
-				downloads_yesterday: total_downloads_yesterday,
-				total: mods.len() as u32,
-				mods,
-				maintained_mods: maintained_mods_infos
-			};
-
-			cache::lock_and_update(&state.author_cache, steamid, author)
-		}
-	};
+	Ok(mods)
+}
 
-	return cached_json!(author, 3600, false);
+#[derive(FromFormField, JsonSchema, Clone, Copy, PartialEq)]
+#[schemars(rename_all = "snake_case")]
+pub enum ModListSort {
+	Rank,
+	Downloads,
+	#[field(value = "downloads_today")]
+	DownloadsToday,
+	#[field(value = "downloads_yesterday")]
+	DownloadsYesterday,
+	Name
 }
 
-#[get("/list")]
-pub async fn list_1_3(state: &State<Api13State>) -> Result<CacheResponse<Value>, APIError> {
-	let cache = {
-		let mod_cache = state.mod_list_cache.lock().unwrap();
-		match mod_cache.expired(3600) {
-			true => Some(mod_cache.item.clone()),
-			false => None
-		}
-	};
+#[derive(FromForm, JsonSchema)]
+pub struct ModListQuery<'r> {
+	#[schemars(with = "ModListSort")]
+	sort: form::Result<'r, ModListSort>,
+	// ascending when sorting by rank or name, descending otherwise
+	#[schemars(with = "SortOrder")]
+	order: form::Result<'r, SortOrder>,
+	// starts at 1
+	#[schemars(with = "u32")]
+	page: form::Result<'r, u32>,
+	// at most 1000
+	#[schemars(with = "u32")]
+	per_page: form::Result<'r, u32>,
+}
 
-	let mods = match cache {
-		Some(cached_value) => cached_value,
-		None => {
-			let mod_selector = &Selector::parse("table > tbody > tr:not(:first-child)")?;
-			let td_selector = &Selector::parse("td")?;
-
-			let mut mods: Vec<ModListInfo> = Vec::new();
-
-			// new scopes because funny errors
-			{
-				let html = get_html(&format!("{API_URL}/modmigrationprogressalltime.php")).await?;
-				let mod_infos = html.select(mod_selector);
-
-				for info in mod_infos {
-					let mut td = info.select(td_selector);
-
-					mods.push(ModListInfo {
-						rank: td.next().unwrap().inner_html().parse().unwrap(),
-						display_name: td.next().unwrap().inner_html(),
-						downloads_total: td.next().unwrap().inner_html().parse().unwrap(),
-						downloads_yesterday: td.next().unwrap().inner_html().parse().unwrap(),
-						mod_version: td.next().unwrap().inner_html(),
-						tmodloader_version: td.next().unwrap().inner_html(),
-
-						internal_name: "<pending>".to_string(),
-						downloads_today: 0,
-					})
-				}
-			}
+impl ModListQuery<'_> {
+	fn apply(self, mut mods: Vec<ModListInfo>) -> Result<Vec<ModListInfo>, APIError> {
+		let sort = optional("sort", self.sort)?;
+		let order = optional("order", self.order)?;
+		let page = optional("page", self.page)?;
+		let per_page = optional("per_page", self.per_page)?;
+
+		let sort = sort.unwrap_or(ModListSort::Rank);
+		let descending = match order {
+			Some(order) => order == SortOrder::Desc,
+			None => !matches!(sort, ModListSort::Rank | ModListSort::Name)
+		};
+		let compare = |a: &ModListInfo, b: &ModListInfo| match sort {
+			ModListSort::Rank => a.rank.cmp(&b.rank),
+			ModListSort::Downloads => a.downloads_total.cmp(&b.downloads_total),
+			ModListSort::DownloadsToday => a.downloads_today.cmp(&b.downloads_today),
+			ModListSort::DownloadsYesterday => a.downloads_yesterday.cmp(&b.downloads_yesterday),
+			ModListSort::Name => a.display_name.chars().flat_map(char::to_lowercase).cmp(b.display_name.chars().flat_map(char::to_lowercase))
+		};
+		// ties stay in rank order either way
+		mods.sort_by(|a, b| {
+			let order = if descending { compare(b, a) } else { compare(a, b) };
+			order.then(a.rank.cmp(&b.rank))
+		});
 
-			{
-				let html = get_html(&format!("{API_URL}/modmigrationprogress.php")).await?;
-				let mod_infos = html.select(mod_selector);
+		if page.is_none() && per_page.is_none() {
+			return Ok(mods);
+		}
+		let per_page = per_page.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE) as usize;
+		let page = page.unwrap_or(1).max(1) as usize;
+		Ok(mods.into_iter().skip((page - 1).saturating_mul(per_page)).take(per_page).collect())
+	}
+}
 
-				for info in mod_infos {
-					let mut td = info.select(td_selector);
+// the number of mods on all pages is sent in the X-Total-Count header
+pub struct ModListResponse(CacheResponse<Value>, usize);
 
-					// get index by searching for the display name in the mods array
-					let mod_name = td.next().unwrap().inner_html();
-					let index = mods.iter().position(|x| x.display_name == mod_name).unwrap();
+impl<'r, 'o: 'r> Responder<'r, 'o> for ModListResponse {
+	fn respond_to(self, req: &'r Request<'_>) -> response::Result<'o> {
+		let mut response = self.0.respond_to(req)?;
+		response.set_header(Header::new("X-Total-Count", self.1.to_string()));
+		Ok(response)
+	}
+}
 
-					// set missing fields
-					mods[index].downloads_today = td.nth(0).unwrap().inner_html().parse().unwrap();
-					mods[index].internal_name = td.nth(2).unwrap().inner_html();
-				}
+#[get("/list?<query..>")]
+pub async fn list_1_3(query: ModListQuery<'_>, state: &State<Api13State>) -> Result<ModListResponse, APIError> {
+	let mods = state.mod_list_cache.get_or_try_insert_with((), || async {
+		match scrape_mod_list(&state.db).await {
+			Ok(mods) => {
+				archive::log_failure(archive::save_mod_list(&mods, &state.db).await, "1.3 mod list");
+				Ok(mods)
 			}
-
-			// update cache value
-			let mut cache = state.mod_list_cache.lock().unwrap();
-			cache.item = mods.clone();
-			cache.time_stamp = std::time::SystemTime::now();
-
-			mods
+			Err(e) => archive::or_archived(e, archive::load_mod_list(&state.db).await, "1.3 mod list")
 		}
-	};
+	}).await?;
 
-	return cached_json!(mods, 7200, false)
+	let total = mods.len();
+	let mods = query.apply(mods)?;
+	let response: Result<CacheResponse<Value>, APIError> = cached_json!(mods, 7200, false);
+	Ok(ModListResponse(response?, total))
 }
 
+pub(super) async fn scrape_history(modname: &str) -> Result<Vec<ModHistory>, APIError> {
+	let html = get_html(&format!("{API_URL}/{DOWNLOAD_HISTORY}?modname={}", modname)).await?;
+	Ok(parse::parse_history(&html)?)
+}
 
-#[get("/history/<modname>")]
-pub async fn history_1_3(modname: &str) -> Result<CacheResponse<Value>, APIError> {
-	let html = get_html(&format!("{API_URL}/tools/moddownloadhistory.php?modname={}", modname)).await?;
-	let versions_selector = &Selector::parse("table > tbody > tr:not(:first-child)")?;
-	let versions = html.select(versions_selector);
-
-	let td_selector = &Selector::parse("td")?;
-
-	let mut history: Vec<ModHistory> = Vec::new();
-	for version in versions {
-		let mut version_data = version.select(td_selector);
-
-		history.push(ModHistory{
-			version: version_data.next().unwrap().inner_html(),
-			downloads_total: version_data.next().unwrap().inner_html().parse().unwrap(),
-			tmodloader_version: version_data.next().unwrap().inner_html(),
-			publish_date: version_data.next().unwrap().inner_html()
-		});
+pub async fn get_history(modname: &str, state: &Api13State) -> Result<Vec<ModHistory>, APIError> {
+	match scrape_history(modname).await {
+		Ok(history) => {
+			archive::log_failure(archive::save_history(modname, &history, &state.db).await, &format!("1.3 history of '{modname}'"));
+			Ok(history)
+		}
+		Err(e) => archive::or_archived(e, archive::load_history(modname, &state.db).await, &format!("1.3 history of '{modname}'"))
 	}
+}
+
+#[get("/history/<modname>")]
+pub async fn history_1_3(modname: &str, state: &State<Api13State>) -> Result<CacheResponse<Value>, APIError> {
+	let history = get_history(modname, state).await?;
 
 	return cached_json!(history, 7200, false);
-}
\ No newline at end of file
+}
@@ -0,0 +1,411 @@
+// the 1.3 data is scraped from javid.ddns.net, which could go offline any day. Everything that was scraped
+// successfully is stored here, and the routes fall back to it when scraping fails
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use rocket::futures::{stream, StreamExt};
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use sqlx::types::Json;
+use crate::APIError;
+use crate::api13::{api, parse};
+use crate::api13::responses::{AuthorInfo, AuthorModInfo, MaintainedModInfo, MergeStatus, ModHistory, ModInfo, ModListInfo};
+use crate::mod_types::ModReference;
+
+// how many mods and authors are scraped at the same time by archive_all
+const CONCURRENT_SCRAPES: usize = 4;
+
+static ARCHIVING: AtomicBool = AtomicBool::new(false);
+
+// clears ARCHIVING when archive_all is done, even if it panicked or was cancelled
+struct ArchivingGuard;
+
+impl Drop for ArchivingGuard {
+	fn drop(&mut self) {
+		ARCHIVING.store(false, Ordering::Release);
+	}
+}
+
+// the archived value if there is one, otherwise the error scraping failed with
+pub fn or_archived<T>(scrape_error: APIError, archived: Result<Option<T>, APIError>, what: &str) -> Result<T, APIError> {
+	match archived {
+		Ok(Some(value)) => {
+			log::warn!("Serving archived {what}, scraping failed: {scrape_error}");
+			Ok(value)
+		}
+		Ok(None) => Err(scrape_error),
+		Err(e) => {
+			log::warn!("Could not read archived {what}: {e}");
+			Err(scrape_error)
+		}
+	}
+}
+
+// archiving is best effort, failing to do so shouldn't fail the request
+pub fn log_failure(result: Result<(), APIError>, what: &str) {
+	if let Err(e) = result {
+		log::warn!("Could not archive {what}: {e}");
+	}
+}
+
+pub async fn save_mod_list(mods: &[ModListInfo], db: &PgPool) -> Result<(), APIError> {
+	let ranks: Vec<i32> = mods.iter().map(|m| m.rank as i32).collect();
+	let display_names: Vec<&str> = mods.iter().map(|m| m.display_name.as_str()).collect();
+	let internal_names: Vec<Option<&str>> = mods.iter().map(|m| m.internal_name.as_deref()).collect();
+	let downloads_total: Vec<i64> = mods.iter().map(|m| m.downloads_total as i64).collect();
+	let downloads_today: Vec<i64> = mods.iter().map(|m| m.downloads_today as i64).collect();
+	let downloads_yesterday: Vec<i64> = mods.iter().map(|m| m.downloads_yesterday as i64).collect();
+	let mod_versions: Vec<&str> = mods.iter().map(|m| m.mod_version.as_str()).collect();
+	let tmodloader_versions: Vec<&str> = mods.iter().map(|m| m.tmodloader_version.as_str()).collect();
+
+	let mut tx = db.begin().await?;
+	sqlx::query!("DELETE FROM legacy_mod_list").execute(&mut *tx).await?;
+	sqlx::query!(
+		r#"
+		INSERT INTO legacy_mod_list (
+			rank, display_name, internal_name, downloads_total, downloads_today,
+			downloads_yesterday, mod_version, tmodloader_version
+		)
+		SELECT * FROM UNNEST($1::int[], $2::text[], $3::text[], $4::bigint[], $5::bigint[], $6::bigint[], $7::text[], $8::text[])
+		ON CONFLICT (rank) DO NOTHING
+		"#,
+		&ranks,
+		&display_names as &[&str],
+		&internal_names as &[Option<&str>],
+		&downloads_total,
+		&downloads_today,
+		&downloads_yesterday,
+		&mod_versions as &[&str],
+		&tmodloader_versions as &[&str]
+	)
+	.execute(&mut *tx)
+	.await?;
+	tx.commit().await?;
+
+	Ok(())
+}
+
+// None if the list was never archived
+pub async fn load_mod_list(db: &PgPool) -> Result<Option<Vec<ModListInfo>>, APIError> {
+	let rows = sqlx::query!(
+		r#"
+		SELECT rank, display_name, internal_name, downloads_total, downloads_today,
+			downloads_yesterday, mod_version, tmodloader_version
+		FROM legacy_mod_list
+		ORDER BY rank
+		"#
+	)
+	.fetch_all(db)
+	.await?;
+
+	if rows.is_empty() {
+		return Ok(None);
+	}
+
+	Ok(Some(rows.into_iter().map(|r| ModListInfo {
+		rank: r.rank as u32,
+		merge_status: if r.internal_name.is_some() { MergeStatus::Archived } else { MergeStatus::Unmatched },
+		internal_name: r.internal_name,
+		display_name: r.display_name,
+		downloads_total: r.downloads_total as u32,
+		downloads_today: r.downloads_today as u32,
+		downloads_yesterday: r.downloads_yesterday as u32,
+		mod_version: r.mod_version,
+		tmodloader_version: r.tmodloader_version,
+	}).collect()))
+}
+
+// the internal names of archived mods by display name, leaving out display names used by more than one mod
+pub async fn archived_names(db: &PgPool) -> Result<HashMap<String, String>, APIError> {
+	let rows = sqlx::query!(
+		r#"
+		SELECT display_name AS "display_name!", MIN(internal_name) AS "internal_name!"
+		FROM (
+			SELECT display_name, internal_name FROM legacy_mods
+			UNION
+			SELECT display_name, internal_name FROM legacy_mod_list WHERE internal_name IS NOT NULL
+		) names
+		GROUP BY display_name
+		HAVING COUNT(DISTINCT internal_name) = 1
+		"#
+	)
+	.fetch_all(db)
+	.await?;
+
+	Ok(rows.into_iter().map(|r| (r.display_name, r.internal_name)).collect())
+}
+
+// the number of mods on modmigrationprogress.php when the list was archived
+pub async fn load_count(db: &PgPool) -> Result<Option<usize>, APIError> {
+	let row = sqlx::query!(
+		r#"SELECT COUNT(*) AS "mods!", COUNT(internal_name) AS "count!" FROM legacy_mod_list"#
+	)
+	.fetch_one(db)
+	.await?;
+
+	Ok((row.mods > 0).then_some(row.count as usize))
+}
+
+// archived in the format of modinfo.php
+fn format_mod_references(references: &[ModReference]) -> String {
+	let references: Vec<String> = references.iter().map(|reference| match &reference.version {
+		Some(version) => format!("{}@{version}", reference.name),
+		None => reference.name.clone()
+	}).collect();
+	references.join(", ")
+}
+
+pub async fn save_mod_info(info: &ModInfo, db: &PgPool) -> Result<(), APIError> {
+	sqlx::query!(
+		r#"
+		INSERT INTO legacy_mods (
+			internal_name, display_name, version, author, download_link, downloads_total, downloads_yesterday,
+			last_updated, tmodloader_version, modreferences, modside, description, homepage, icon
+		)
+		VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
+		ON CONFLICT (internal_name) DO UPDATE SET
+			display_name = EXCLUDED.display_name,
+			version = EXCLUDED.version,
+			author = EXCLUDED.author,
+			download_link = EXCLUDED.download_link,
+			downloads_total = EXCLUDED.downloads_total,
+			downloads_yesterday = EXCLUDED.downloads_yesterday,
+			last_updated = EXCLUDED.last_updated,
+			tmodloader_version = EXCLUDED.tmodloader_version,
+			modreferences = EXCLUDED.modreferences,
+			modside = EXCLUDED.modside,
+			description = EXCLUDED.description,
+			homepage = EXCLUDED.homepage,
+			icon = EXCLUDED.icon,
+			archived_at = NOW()
+		"#,
+		info.name,
+		info.displayname,
+		info.version,
+		info.author,
+		info.download,
+		info.downloads as i64,
+		info.hot as i64,
+		info.updateTimeStamp,
+		info.modloaderversion,
+		format_mod_references(&info.modreferences),
+		info.modside.to_string(),
+		info.description,
+		info.homepage,
+		info.icon
+	)
+	.execute(db)
+	.await?;
+
+	Ok(())
+}
+
+pub async fn load_mod_info(modname: &str, db: &PgPool) -> Result<Option<ModInfo>, APIError> {
+	let row = sqlx::query!(
+		r#"
+		SELECT internal_name, display_name, version, author, download_link, downloads_total, downloads_yesterday,
+			last_updated, tmodloader_version, modreferences, modside, description, homepage, icon
+		FROM legacy_mods
+		WHERE internal_name = $1
+		"#,
+		modname
+	)
+	.fetch_optional(db)
+	.await?;
+
+	Ok(row.map(|r| ModInfo {
+		displayname: r.display_name,
+		name: r.internal_name,
+		version: r.version,
+		author: r.author,
+		download: r.download_link,
+		downloads: r.downloads_total as u32,
+		hot: r.downloads_yesterday as u32,
+		updateTimeStamp: r.last_updated,
+		modloaderversion: r.tmodloader_version,
+		modreferences: parse::parse_mod_references(&r.modreferences),
+		modside: r.modside.parse().unwrap_or_default(),
+		description: r.description,
+		homepage: r.homepage,
+		icon: r.icon,
+	}))
+}
+
+pub async fn save_history(modname: &str, history: &[ModHistory], db: &PgPool) -> Result<(), APIError> {
+	let positions: Vec<i32> = (0..history.len() as i32).collect();
+	let versions: Vec<&str> = history.iter().map(|h| h.version.as_str()).collect();
+	let downloads_total: Vec<i64> = history.iter().map(|h| h.downloads_total as i64).collect();
+	let tmodloader_versions: Vec<&str> = history.iter().map(|h| h.tmodloader_version.as_str()).collect();
+	let publish_dates: Vec<DateTime<Utc>> = history.iter().map(|h| h.publish_date).collect();
+
+	let mut tx = db.begin().await?;
+	sqlx::query!("DELETE FROM legacy_mod_history WHERE internal_name = $1", modname).execute(&mut *tx).await?;
+	sqlx::query!(
+		r#"
+		INSERT INTO legacy_mod_history (internal_name, position, version, downloads_total, tmodloader_version, publish_date)
+		SELECT $1, * FROM UNNEST($2::int[], $3::text[], $4::bigint[], $5::text[], $6::timestamptz[])
+		"#,
+		modname,
+		&positions,
+		&versions as &[&str],
+		&downloads_total,
+		&tmodloader_versions as &[&str],
+		&publish_dates
+	)
+	.execute(&mut *tx)
+	.await?;
+	tx.commit().await?;
+
+	Ok(())
+}
+
+// None if the history of the mod was never archived
+pub async fn load_history(modname: &str, db: &PgPool) -> Result<Option<Vec<ModHistory>>, APIError> {
+	let rows = sqlx::query!(
+		r#"
+		SELECT version, downloads_total, tmodloader_version, publish_date
+		FROM legacy_mod_history
+		WHERE internal_name = $1
+		ORDER BY position
+		"#,
+		modname
+	)
+	.fetch_all(db)
+	.await?;
+
+	if rows.is_empty() {
+		return Ok(None);
+	}
+
+	let mut history: Vec<ModHistory> = rows.into_iter().map(|r| ModHistory {
+		version: r.version,
+		downloads_total: r.downloads_total as u32,
+		tmodloader_version: r.tmodloader_version,
+		publish_date: r.publish_date,
+		downloads_delta: None,
+	}).collect();
+	parse::add_download_deltas(&mut history);
+
+	Ok(Some(history))
+}
+
+pub async fn save_author(author: &AuthorInfo, db: &PgPool) -> Result<(), APIError> {
+	let steam_id: i64 = author.steam_id.parse().unwrap_or_default();
+	sqlx::query!(
+		r#"
+		INSERT INTO legacy_authors (steam_id, steam_name, steam_avatar, mods, maintained_mods)
+		VALUES ($1, $2, $3, $4, $5)
+		ON CONFLICT (steam_id) DO UPDATE SET
+			steam_name = EXCLUDED.steam_name,
+			steam_avatar = EXCLUDED.steam_avatar,
+			mods = EXCLUDED.mods,
+			maintained_mods = EXCLUDED.maintained_mods,
+			archived_at = NOW()
+		"#,
+		steam_id,
+		author.steam_name,
+		author.steam_avatar,
+		Json(&author.mods) as _,
+		Json(&author.maintained_mods) as _
+	)
+	.execute(db)
+	.await?;
+
+	Ok(())
+}
+
+pub async fn load_author(steamid: u64, db: &PgPool) -> Result<Option<AuthorInfo>, APIError> {
+	let row = sqlx::query!(
+		r#"
+		SELECT steam_name, steam_avatar,
+			mods AS "mods: Json<Vec<AuthorModInfo>>",
+			maintained_mods AS "maintained_mods: Json<Vec<MaintainedModInfo>>"
+		FROM legacy_authors
+		WHERE steam_id = $1
+		"#,
+		steamid as i64
+	)
+	.fetch_optional(db)
+	.await?;
+
+	Ok(row.map(|r| api::author_info(steamid, r.steam_name, r.steam_avatar, r.mods.0, r.maintained_mods.0)))
+}
+
+// authors whose ranks are archived: the ones archived before and the authors of 1.4 mods,
+// most of them also made 1.3 mods
+async fn known_authors(db: &PgPool) -> Result<Vec<u64>, APIError> {
+	let ids = sqlx::query_scalar!(
+		r#"
+		SELECT steam_id AS "steam_id!" FROM legacy_authors
+		UNION
+		SELECT DISTINCT author_id FROM mods
+		"#
+	)
+	.fetch_all(db)
+	.await?;
+
+	Ok(ids.into_iter().map(|id| id as u64).collect())
+}
+
+// scrapes and archives the mod list, the info and history of every mod on it and the ranks of all known authors.
+// Takes a while, the mods are scraped a few at a time to go easy on the server
+pub async fn archive_all(db: &PgPool, steam_api_key: &str) -> Result<(), APIError> {
+	if ARCHIVING.swap(true, Ordering::AcqRel) {
+		log::warn!("The 1.3 archive is already being updated");
+		return Ok(());
+	}
+
+	let _guard = ArchivingGuard;
+	archive_everything(db, steam_api_key).await
+}
+
+async fn archive_everything(db: &PgPool, steam_api_key: &str) -> Result<(), APIError> {
+	log::info!("Archiving the 1.3 mod list");
+	let mods = api::scrape_mod_list(db).await?;
+	save_mod_list(&mods, db).await?;
+
+	// the info and history can only be scraped for mods with a known internal name
+	let names: Vec<String> = mods.into_iter().filter_map(|m| m.internal_name).collect();
+
+	log::info!("Archiving {} 1.3 mods", names.len());
+	let failed = stream::iter(names)
+		.map(|name| async move {
+			let info = api::scrape_mod_info(&name).await;
+			let info = match info {
+				Ok(info) => save_mod_info(&info, db).await,
+				Err(e) => Err(e)
+			};
+			let history = match api::scrape_history(&name).await {
+				Ok(history) => save_history(&name, &history, db).await,
+				Err(e) => Err(e)
+			};
+
+			match info.and(history) {
+				Ok(()) => 0,
+				Err(e) => {
+					log::warn!("Could not archive the 1.3 mod '{name}': {e}");
+					1
+				}
+			}
+		})
+		.buffer_unordered(CONCURRENT_SCRAPES)
+		.fold(0, |failed, result| async move { failed + result })
+		.await;
+
+	let authors = known_authors(db).await?;
+	log::info!("Archiving the 1.3 ranks of {} authors", authors.len());
+	let failed_authors = stream::iter(authors)
+		.map(|steamid| async move {
+			match api::scrape_author_info(steamid, steam_api_key).await {
+				// most 1.4 authors never made a 1.3 mod
+				Ok(author) if author.mods.is_empty() && author.maintained_mods.is_empty() => Ok(()),
+				Ok(author) => save_author(&author, db).await,
+				Err(e) => Err(e)
+			}
+		})
+		.buffer_unordered(CONCURRENT_SCRAPES)
+		.fold(0, |failed, result| async move { failed + result.is_err() as usize })
+		.await;
+
+	log::info!("Finished archiving 1.3, {failed} mods and {failed_authors} authors failed");
+	Ok(())
+}
@@ -0,0 +1,71 @@
+// the 1.3 server has no icon for most mods, and it won't be around forever. If ICON_MIRROR_DIR is set,
+// icons are downloaded into it the first time they're found and served from /1.3/icons/
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use rocket::tokio::fs;
+
+pub const ROUTE: &str = "/1.3/icons/";
+
+// created here rather than when the server starts, so the archive command can mirror into a fresh directory too
+pub static MIRROR_DIR: LazyLock<Option<PathBuf>> = LazyLock::new(|| {
+	let dir = PathBuf::from(std::env::var_os("ICON_MIRROR_DIR")?);
+	match std::fs::create_dir_all(&dir) {
+		Ok(()) => Some(dir),
+		Err(e) => {
+			log::error!("Could not create the 'ICON_MIRROR_DIR' directory, icons won't be mirrored: {e}");
+			None
+		}
+	}
+});
+
+// where the server can be reached from outside, e.g. "https://tmlapis.example.com". Without it the urls of
+// mirrored icons are relative to the server
+static PUBLIC_URL: LazyLock<String> = LazyLock::new(|| std::env::var("PUBLIC_URL").unwrap_or_default().trim_end_matches('/').to_owned());
+
+fn mirrored_url(file_name: &str) -> String {
+	format!("{}{ROUTE}{file_name}", *PUBLIC_URL)
+}
+
+// the name comes from the request, so it can't be trusted as part of a path
+fn is_safe_file_name(name: &str) -> bool {
+	!name.starts_with('.') && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+}
+
+// the url of the icon, or None if the mod has no icon
+pub async fn icon_url(client: &reqwest::Client, upstream_url: String, modname: &str, version: &str) -> Option<String> {
+	let file_name = format!("{modname}_{version}.png");
+	let mirror = MIRROR_DIR.as_ref().filter(|_| is_safe_file_name(&file_name)).map(|dir| dir.join(&file_name));
+
+	if let Some(path) = &mirror && fs::try_exists(path).await.unwrap_or(false) {
+		return Some(mirrored_url(&file_name));
+	}
+
+	let res = match client.get(&upstream_url).send().await {
+		Ok(res) if res.status().is_success() => res,
+		Ok(_) => return None,
+		Err(e) => {
+			log::warn!("Could not check the icon of '{modname}': {}", e.without_url());
+			return None;
+		}
+	};
+
+	let Some(path) = mirror else {
+		return Some(upstream_url);
+	};
+	match mirror_icon(res, &path).await {
+		Ok(()) => Some(mirrored_url(&file_name)),
+		Err(e) => {
+			log::warn!("Could not mirror the icon of '{modname}': {e}");
+			Some(upstream_url)
+		}
+	}
+}
+
+async fn mirror_icon(res: reqwest::Response, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+	let bytes = res.bytes().await?;
+	// written to a temporary file first, so a half written icon is never served
+	let partial = path.with_extension("part");
+	fs::write(&partial, &bytes).await?;
+	fs::rename(&partial, path).await?;
+	Ok(())
+}
@@ -1,27 +1,43 @@
 pub mod api;
-mod responses;
+pub mod archive;
+pub mod icons;
+pub mod responses;
+mod scrape;
+pub mod parse;
 
-use std::sync::{Mutex, Arc};
+use std::sync::Arc;
+use std::time::Duration;
 use responses::{AuthorInfo, ModInfo, ModListInfo};
-use crate::cache::{CacheMap, CacheItem};
+use sqlx::PgPool;
+use crate::cache::{Cache, CacheAdmin, CacheFactory};
+use crate::name_cache::NameCache;
 use rocket::response::content::RawHtml;
 
 pub struct Api13State {
 	pub steam_api_key: Arc<String>,
-	pub author_cache: Arc<Mutex<CacheMap<u64, AuthorInfo>>>,
-	pub mod_cache: Arc<Mutex<CacheMap<String, ModInfo>>>,
-	pub mod_list_cache: Arc<Mutex<CacheItem<Vec<ModListInfo>>>>
+	pub db: Arc<PgPool>,
+	pub name_cache: Arc<NameCache>,
+	pub author_cache: Arc<Cache<u64, AuthorInfo>>,
+	pub mod_cache: Arc<Cache<String, ModInfo>>,
+	pub mod_list_cache: Arc<Cache<(), Vec<ModListInfo>>>
 }
 
 impl Api13State {
-	pub fn init(steam_api_key: Arc<String>) -> Api13State {
+	pub fn init(steam_api_key: Arc<String>, db: Arc<PgPool>, name_cache: Arc<NameCache>, caches: &CacheFactory) -> Api13State {
+		let revalidated = caches.stale_while_revalidate();
 		Api13State {
 			steam_api_key,
-			author_cache: Arc::new(Mutex::new(CacheMap::new())),
-			mod_cache: Arc::new(Mutex::new(CacheMap::new())),
-			mod_list_cache: Arc::new(Mutex::new(CacheItem::new()))
+			db,
+			name_cache,
+			author_cache: revalidated.shared("1.3_author", Duration::from_secs(3600), 1000),
+			mod_cache: revalidated.local("1.3_mod", Duration::from_secs(3600), 5000),
+			mod_list_cache: caches.local("1.3_list", Duration::from_secs(3600), 1)
 		}
 	}
+
+	pub fn caches(&self) -> Vec<Arc<dyn CacheAdmin>> {
+		vec![self.author_cache.clone(), self.mod_cache.clone(), self.mod_list_cache.clone()]
+	}
 }
 
 #[get("/")]
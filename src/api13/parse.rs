@@ -0,0 +1,466 @@
+// parsers for the pages of the 1.3 server, kept apart from fetching them so they can be tested with saved pages
+use std::collections::HashMap;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use rocket::serde::json::serde_json::{self, Value};
+use scraper::Html;
+use crate::api13::responses::{AuthorModInfo, MaintainedModInfo, MergeStatus, ModHistory, ModInfo, ModListInfo};
+use crate::api13::scrape::{ScrapeError, Table};
+use crate::mod_types::ModReference;
+
+pub const MIGRATION_PROGRESS: &str = "modmigrationprogress.php";
+pub const MIGRATION_PROGRESS_ALL_TIME: &str = "modmigrationprogressalltime.php";
+pub const RANKS_BY_STEAMID: &str = "tools/ranksbysteamid.php";
+pub const DOWNLOAD_HISTORY: &str = "tools/moddownloadhistory.php";
+
+// the format of timestamps on the 1.3 server. The pages don't say which time zone they're in, UTC is assumed
+// but hasn't been checked against a mod with a known publish time
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+// a row of modmigrationprogress.php, which has the fields modmigrationprogressalltime.php is missing
+pub struct TodaysDownloads {
+	pub display_name: String,
+	pub downloads_today: u32,
+	pub version: String,
+	pub internal_name: String,
+}
+
+// number of mods on modmigrationprogress.php
+pub fn parse_count(html: &Html) -> Result<usize, ScrapeError> {
+	Ok(Table::all(html, MIGRATION_PROGRESS, "mods")?.row_count())
+}
+
+pub fn parse_todays_downloads(html: &Html) -> Result<Vec<TodaysDownloads>, ScrapeError> {
+	Table::all(html, MIGRATION_PROGRESS, "mods")?.parse_rows(|row| Ok(TodaysDownloads {
+		display_name: row.text(0, "display_name")?,
+		downloads_today: row.parse(1, "downloads_today")?,
+		version: row.text(2, "version")?,
+		internal_name: row.text(4, "internal_name")?,
+	}))
+}
+
+// internal_name and downloads_today are filled in by merge_mod_list
+pub fn parse_mod_list(html: &Html) -> Result<Vec<ModListInfo>, ScrapeError> {
+	Table::all(html, MIGRATION_PROGRESS_ALL_TIME, "mods")?.parse_rows(|row| Ok(ModListInfo {
+		rank: row.parse(0, "rank")?,
+		display_name: row.text(1, "display_name")?,
+		downloads_total: row.parse(2, "downloads_total")?,
+		downloads_yesterday: row.parse(3, "downloads_yesterday")?,
+		mod_version: row.text(4, "mod_version")?,
+		tmodloader_version: row.text(5, "tmodloader_version")?,
+
+		internal_name: None,
+		downloads_today: 0,
+		merge_status: MergeStatus::Unmatched,
+	}))
+}
+
+// modmigrationprogress.php leaves out the "v" of versions
+fn normalize_version(version: &str) -> &str {
+	let version = version.trim();
+	version.strip_prefix('v').unwrap_or(version)
+}
+
+fn set_matched(m: &mut ModListInfo, row: TodaysDownloads) {
+	m.internal_name = Some(row.internal_name);
+	m.downloads_today = row.downloads_today;
+	m.merge_status = MergeStatus::Matched;
+}
+
+// display names aren't unique, so rows are matched by display name and version. A mod updated between
+// loading the two pages has different versions on them, those are matched by display name if only one
+// unmatched mod has it. Returns the rows which didn't match any mod
+pub fn merge_mod_list(mods: &mut [ModListInfo], today: Vec<TodaysDownloads>) -> Vec<TodaysDownloads> {
+	let mut by_version: HashMap<(String, String), Vec<usize>> = HashMap::new();
+	// reversed, so pop returns the best ranked mod first
+	for (i, m) in mods.iter().enumerate().rev() {
+		by_version.entry((m.display_name.clone(), normalize_version(&m.mod_version).to_owned())).or_default().push(i);
+	}
+
+	let mut other_version = Vec::new();
+	for row in today {
+		let key = (row.display_name.clone(), normalize_version(&row.version).to_owned());
+		match by_version.get_mut(&key).and_then(Vec::pop) {
+			Some(i) => set_matched(&mut mods[i], row),
+			None => other_version.push(row)
+		}
+	}
+
+	let mut unmatched = Vec::new();
+	for row in other_version {
+		let mut candidates = mods.iter_mut()
+			.filter(|m| m.merge_status == MergeStatus::Unmatched && m.display_name == row.display_name);
+		match (candidates.next(), candidates.next()) {
+			(Some(m), None) => set_matched(m, row),
+			_ => unmatched.push(row)
+		}
+	}
+	unmatched
+}
+
+// mods which weren't downloaded today get the internal name they were archived with,
+// as long as their display name is unique
+pub fn fill_archived_names(mods: &mut [ModListInfo], archived: &HashMap<String, String>) {
+	let mut display_names: HashMap<String, usize> = HashMap::new();
+	for m in mods.iter() {
+		*display_names.entry(m.display_name.clone()).or_default() += 1;
+	}
+
+	for m in mods.iter_mut().filter(|m| m.merge_status == MergeStatus::Unmatched && display_names[&m.display_name] == 1) {
+		if let Some(name) = archived.get(&m.display_name) {
+			m.internal_name = Some(name.clone());
+			m.merge_status = MergeStatus::Archived;
+		}
+	}
+}
+
+// the mods of an author and the mods they maintain. There are 4 tables,
+// the first one has the mods of the author and the last one the maintained mods
+pub fn parse_author_mods(html: &Html) -> Result<(Vec<AuthorModInfo>, Vec<MaintainedModInfo>), ScrapeError> {
+	let mods = Table::nth(html, RANKS_BY_STEAMID, "mods", 0)?.parse_rows(|row| Ok(AuthorModInfo {
+		rank: row.parse(0, "rank")?,
+		display_name: row.text(1, "display_name")?,
+		downloads_total: row.parse(2, "downloads_total")?,
+		downloads_yesterday: row.parse(3, "downloads_yesterday")?,
+	}))?;
+
+	let maintained_mods = Table::last(html, RANKS_BY_STEAMID, "maintained mods")?.parse_rows(|row| Ok(MaintainedModInfo {
+		internal_name: row.text(0, "internal_name")?,
+		downloads_total: row.parse(1, "downloads_total")?,
+		downloads_yesterday: row.parse(2, "downloads_yesterday")?,
+	}))?;
+
+	Ok((mods, maintained_mods))
+}
+
+pub fn parse_history(html: &Html) -> Result<Vec<ModHistory>, ScrapeError> {
+	let mut history = Table::all(html, DOWNLOAD_HISTORY, "versions")?.parse_rows(|row| Ok(ModHistory {
+		version: row.text(0, "version")?,
+		downloads_total: row.parse(1, "downloads_total")?,
+		tmodloader_version: row.text(2, "tmodloader_version")?,
+		publish_date: row.parse_with(3, "publish_date", parse_timestamp)?,
+		downloads_delta: None,
+	}))?;
+
+	add_download_deltas(&mut history);
+	Ok(history)
+}
+
+// publish dates and the last_updated of mod info
+pub fn parse_timestamp(text: &str) -> chrono::ParseResult<DateTime<Utc>> {
+	NaiveDateTime::parse_from_str(text.trim(), TIMESTAMP_FORMAT).map(|date| date.and_utc())
+}
+
+// the page lists the newest version first, but the order isn't relied on
+pub fn add_download_deltas(history: &mut [ModHistory]) {
+	let mut by_date: Vec<usize> = (0..history.len()).collect();
+	by_date.sort_by_key(|&i| history[i].publish_date);
+
+	for pair in by_date.windows(2) {
+		let (older, newer) = (pair[0], pair[1]);
+		history[newer].downloads_delta = Some(history[newer].downloads_total as i64 - history[older].downloads_total as i64);
+	}
+}
+
+// the json of tools/modinfo.php, None if there's no mod by the name. Unknown names are assumed to get a body
+// without a mod in it, empty or what php's json_encode makes of a missing row (null, false, [] or {}), it hasn't
+// been captured yet. Anything else has to be a mod, so a changed format is an error rather than a missing mod
+pub fn parse_mod_info(body: &str) -> Result<Option<ModInfo>, serde_json::Error> {
+	let body = body.trim();
+	if body.is_empty() {
+		return Ok(None);
+	}
+	match serde_json::from_str(body)? {
+		Value::Null | Value::Bool(false) => Ok(None),
+		Value::Array(values) if values.is_empty() => Ok(None),
+		Value::Object(fields) if fields.is_empty() => Ok(None),
+		value => serde_json::from_value(value).map(Some)
+	}
+}
+
+// "ModA@1.0, ModB", the format of build.txt
+pub fn parse_mod_references(references: &str) -> Vec<ModReference> {
+	references.split(',')
+		.map(str::trim)
+		.filter(|reference| !reference.is_empty())
+		.map(|reference| match reference.split_once('@') {
+			Some((name, version)) => ModReference { name: name.trim().to_owned(), version: Some(version.trim().to_owned()) },
+			None => ModReference { name: reference.to_owned(), version: None }
+		})
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// hand-written pages with known values, the real ones are checked by the captured_* tests
+	fn fixture(name: &str) -> Html {
+		let path = format!("{}/tests/fixtures/api13/{name}", env!("CARGO_MANIFEST_DIR"));
+		Html::parse_document(&std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("could not read {path}: {e}")))
+	}
+
+	// a page saved from the 1.3 server by tests/fixtures/api13/capture.sh, the tests of pages which
+	// haven't been captured are skipped
+	fn captured(name: &str) -> Option<String> {
+		let path = format!("{}/tests/fixtures/api13/captured/{name}", env!("CARGO_MANIFEST_DIR"));
+		let page = std::fs::read_to_string(path).ok();
+		if page.is_none() {
+			eprintln!("{name} hasn't been captured, run tests/fixtures/api13/capture.sh");
+		}
+		page
+	}
+
+	fn table(rows: &str) -> Html {
+		Html::parse_document(&format!("<table><tr><th>header</th></tr>{rows}</table>"))
+	}
+
+	#[test]
+	fn count() {
+		assert_eq!(parse_count(&fixture("modmigrationprogress.html")).unwrap(), 3);
+	}
+
+	#[test]
+	fn todays_downloads() {
+		let today = parse_todays_downloads(&fixture("modmigrationprogress.html")).unwrap();
+		assert_eq!(today.len(), 3);
+		assert_eq!(today[0].display_name, "Calamity Mod");
+		assert_eq!(today[0].downloads_today, 1520);
+		assert_eq!(today[0].internal_name, "CalamityMod");
+	}
+
+	#[test]
+	fn mod_list() {
+		let mods = parse_mod_list(&fixture("modmigrationprogressalltime.html")).unwrap();
+		assert_eq!(mods.len(), 4);
+
+		let thorium = &mods[1];
+		assert_eq!(thorium.rank, 2);
+		assert_eq!(thorium.display_name, "Thorium Mod");
+		assert_eq!(thorium.downloads_total, 5120399);
+		assert_eq!(thorium.downloads_yesterday, 902);
+		assert_eq!(thorium.mod_version, "v1.6.4.3");
+		assert_eq!(thorium.tmodloader_version, "tModLoader v0.11.8.9");
+	}
+
+	#[test]
+	fn merged_mod_list() {
+		let mut mods = parse_mod_list(&fixture("modmigrationprogressalltime.html")).unwrap();
+		let unmatched = merge_mod_list(&mut mods, parse_todays_downloads(&fixture("modmigrationprogress.html")).unwrap());
+		assert!(unmatched.is_empty());
+
+		assert_eq!(mods[2].internal_name.as_deref(), Some("MagicStorage"));
+		assert_eq!(mods[2].downloads_today, 412);
+		assert_eq!(mods[2].merge_status, MergeStatus::Matched);
+		// not downloaded today
+		assert_eq!(mods[3].internal_name, None);
+		assert_eq!(mods[3].downloads_today, 0);
+		assert_eq!(mods[3].merge_status, MergeStatus::Unmatched);
+	}
+
+	fn list_entry(rank: u32, display_name: &str, version: &str) -> ModListInfo {
+		ModListInfo {
+			rank,
+			internal_name: None,
+			display_name: display_name.to_owned(),
+			downloads_total: 0,
+			downloads_today: 0,
+			downloads_yesterday: 0,
+			mod_version: version.to_owned(),
+			tmodloader_version: "tModLoader v0.11.8.9".to_owned(),
+			merge_status: MergeStatus::Unmatched,
+		}
+	}
+
+	fn today(display_name: &str, version: &str, internal_name: &str) -> TodaysDownloads {
+		TodaysDownloads {
+			display_name: display_name.to_owned(),
+			downloads_today: 1,
+			version: version.to_owned(),
+			internal_name: internal_name.to_owned(),
+		}
+	}
+
+	#[test]
+	fn duplicate_display_names_are_matched_by_version() {
+		let mut mods = vec![list_entry(1, "Extra Things", "v1.0"), list_entry(2, "Extra Things", "v2.3")];
+		let unmatched = merge_mod_list(&mut mods, vec![
+			today("Extra Things", "2.3", "ExtraThings2"),
+			today("Extra Things", "1.0", "ExtraThings"),
+		]);
+
+		assert!(unmatched.is_empty());
+		assert_eq!(mods[0].internal_name.as_deref(), Some("ExtraThings"));
+		assert_eq!(mods[1].internal_name.as_deref(), Some("ExtraThings2"));
+	}
+
+	#[test]
+	fn updated_mods_are_matched_by_display_name() {
+		let mut mods = vec![list_entry(1, "Fargo's Mod", "v4.1"), list_entry(2, "Twins", "v1.0"), list_entry(3, "Twins", "v1.1")];
+		let unmatched = merge_mod_list(&mut mods, vec![
+			today("Fargo's Mod", "4.2", "Fargowiltas"),
+			// ambiguous
+			today("Twins", "1.2", "Twins"),
+			today("Renamed Mod", "1.0", "RenamedMod"),
+		]);
+
+		assert_eq!(mods[0].internal_name.as_deref(), Some("Fargowiltas"));
+		assert_eq!(mods[1].merge_status, MergeStatus::Unmatched);
+		assert_eq!(mods[2].merge_status, MergeStatus::Unmatched);
+		let unmatched: Vec<&str> = unmatched.iter().map(|row| row.internal_name.as_str()).collect();
+		assert_eq!(unmatched, ["Twins", "RenamedMod"]);
+	}
+
+	#[test]
+	fn archived_names_fill_unique_display_names() {
+		let mut mods = vec![list_entry(1, "Boss Checklist", "v1.2.1"), list_entry(2, "Twins", "v1.0"), list_entry(3, "Twins", "v1.1")];
+		let archived = HashMap::from([
+			("Boss Checklist".to_owned(), "BossChecklist".to_owned()),
+			("Twins".to_owned(), "Twins".to_owned()),
+		]);
+		fill_archived_names(&mut mods, &archived);
+
+		assert_eq!(mods[0].internal_name.as_deref(), Some("BossChecklist"));
+		assert_eq!(mods[0].merge_status, MergeStatus::Archived);
+		assert_eq!(mods[1].internal_name, None);
+	}
+
+	#[test]
+	fn author_mods() {
+		let (mods, maintained_mods) = parse_author_mods(&fixture("ranksbysteamid.html")).unwrap();
+
+		assert_eq!(mods.len(), 2);
+		assert_eq!(mods[1].rank, 57);
+		assert_eq!(mods[1].display_name, "Recipe Browser");
+		assert_eq!(mods[1].downloads_total, 402113);
+		assert_eq!(mods[1].downloads_yesterday, 96);
+
+		assert_eq!(maintained_mods.len(), 3);
+		assert_eq!(maintained_mods[2].internal_name, "AutoTrash");
+		assert_eq!(maintained_mods[2].downloads_total, 120442);
+		assert_eq!(maintained_mods[2].downloads_yesterday, 12);
+	}
+
+	#[test]
+	fn history() {
+		let history = parse_history(&fixture("moddownloadhistory.html")).unwrap();
+		assert_eq!(history.len(), 3);
+		assert_eq!(history[0].version, "v0.5.7.10");
+		assert_eq!(history[0].downloads_total, 1203311);
+		assert_eq!(history[0].tmodloader_version, "tModLoader v0.11.8.9");
+		assert_eq!(history[0].publish_date.to_rfc3339(), "2022-03-18T19:02:11+00:00");
+	}
+
+	#[test]
+	fn download_deltas() {
+		let history = parse_history(&fixture("moddownloadhistory.html")).unwrap();
+		let deltas: Vec<Option<i64>> = history.iter().map(|h| h.downloads_delta).collect();
+		assert_eq!(deltas, [Some(400197), Some(579063), None]);
+	}
+
+	#[test]
+	fn mod_info() {
+		let body = r#"{
+			"displayname": "Calamity Mod", "name": "CalamityMod", "version": "v1.4.5.007", "author": "Fabsol",
+			"download": "http://javid.ddns.net/tModLoader/download.php?Down=mods/CalamityMod.tmod", "downloads": 5000000,
+			"hot": 1520, "updateTimeStamp": "2020-12-11 02:45:24", "modloaderversion": "tModLoader v0.11.7.7",
+			"modreferences": "ThoriumMod, CalamityModMusic@1.4.2", "modside": "Both"
+		}"#;
+		let info = parse_mod_info(body).unwrap().unwrap();
+		assert_eq!(info.name, "CalamityMod");
+		assert_eq!(info.downloads, 5000000);
+		assert_eq!(info.modreferences.len(), 2);
+	}
+
+	#[test]
+	fn unknown_mod_info() {
+		for body in ["", " \n", "null", "false", "[]", "{}"] {
+			assert!(parse_mod_info(body).unwrap().is_none(), "{body:?}");
+		}
+	}
+
+	#[test]
+	fn changed_mod_info_is_an_error() {
+		assert!(parse_mod_info("<html><body>Maintenance</body></html>").is_err());
+		assert!(parse_mod_info(r#"{"name": "CalamityMod"}"#).is_err());
+	}
+
+	#[test]
+	fn captured_mod_list() {
+		let (Some(all_time), Some(today)) = (captured("modmigrationprogressalltime.html"), captured("modmigrationprogress.html")) else {
+			return;
+		};
+		let today = Html::parse_document(&today);
+		assert!(parse_count(&today).unwrap() > 0);
+
+		let mut mods = parse_mod_list(&Html::parse_document(&all_time)).unwrap();
+		assert!(!mods.is_empty());
+		assert!(mods.iter().enumerate().all(|(i, m)| m.rank as usize == i + 1 && !m.display_name.is_empty()));
+
+		let today = parse_todays_downloads(&today).unwrap();
+		assert!(!today.is_empty());
+		merge_mod_list(&mut mods, today);
+		assert!(mods.iter().any(|m| m.merge_status == MergeStatus::Matched));
+	}
+
+	#[test]
+	fn captured_author_mods() {
+		let Some(page) = captured("ranksbysteamid.html") else {
+			return;
+		};
+		let (mods, _) = parse_author_mods(&Html::parse_document(&page)).unwrap();
+		assert!(!mods.is_empty());
+		assert!(mods.iter().all(|m| !m.display_name.is_empty()));
+	}
+
+	#[test]
+	fn captured_history() {
+		let Some(page) = captured("moddownloadhistory.html") else {
+			return;
+		};
+		let history = parse_history(&Html::parse_document(&page)).unwrap();
+		assert!(!history.is_empty());
+	}
+
+	#[test]
+	fn captured_mod_info() {
+		if let Some(body) = captured("modinfo.json") {
+			assert!(parse_mod_info(&body).unwrap().is_some());
+		}
+		if let Some(body) = captured("modinfo-unknown.json") {
+			assert!(parse_mod_info(&body).unwrap().is_none());
+		}
+	}
+
+	#[test]
+	fn mod_references() {
+		assert_eq!(parse_mod_references(""), []);
+		assert_eq!(parse_mod_references("ThoriumMod, CalamityMod@1.4.2,"), [
+			ModReference { name: "ThoriumMod".to_owned(), version: None },
+			ModReference { name: "CalamityMod".to_owned(), version: Some("1.4.2".to_owned()) },
+		]);
+	}
+
+	#[test]
+	fn malformed_rows_are_skipped() {
+		let html = table(r#"
+			<tr><td>v1.0</td><td>many</td><td>tModLoader v0.11</td><td>2020-01-01 00:00:00</td></tr>
+			<tr><td>v1.1</td><td>20</td></tr>
+			<tr><td>v1.2</td><td>30</td><td>tModLoader v0.11</td><td>2020-01-03 00:00:00</td></tr>
+			<tr><td>v1.3</td><td>40</td><td>tModLoader v0.11</td><td>yesterday</td></tr>
+		"#);
+
+		let history = parse_history(&html).unwrap();
+		assert_eq!(history.len(), 1);
+		assert_eq!(history[0].version, "v1.2");
+	}
+
+	#[test]
+	fn changed_page_is_an_error() {
+		let html = table("<tr><td>v1.0</td><td>many</td><td>tModLoader v0.11</td><td>2020-01-01 00:00:00</td></tr>");
+		let error = parse_history(&html).err().unwrap().to_string();
+		assert_eq!(error, "tools/moddownloadhistory.php: table 'versions', row 1, column 'downloads_total': could not parse 'many': invalid digit found in string");
+
+		let error = parse_author_mods(&Html::parse_document("<p>no mods</p>")).err().unwrap().to_string();
+		assert_eq!(error, "tools/ranksbysteamid.php: table 'mods': table 1 is missing, the page has 0 tables");
+	}
+}
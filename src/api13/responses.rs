@@ -1,16 +1,12 @@
-use rocket::serde::{Deserialize, Serialize};
+use chrono::{DateTime, Utc};
+use rocket::serde::{Deserialize, Deserializer, Serialize};
+use schemars::JsonSchema;
+use crate::api13::parse;
+use crate::mod_types::{ModReference, ModSide};
 
-#[derive(Serialize, Deserialize, Clone)]
-#[serde(crate = "rocket::serde")]
-pub enum ModSide {
-	Both,
-	Client,
-	Server,
-	NoSync
-}
-
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
 #[serde(crate = "rocket::serde")]
+#[schemars(rename = "ModInfo_1_3")]
 #[allow(non_snake_case)]
 pub struct ModInfo {
 	#[serde(rename(serialize = "display_name"))] pub displayname: String,
@@ -22,13 +18,28 @@ pub struct ModInfo {
 	#[serde(rename(serialize = "downloads_yesterday"))] pub hot: u32,
 	#[serde(rename(serialize = "last_updated"))] pub updateTimeStamp: String,
 	#[serde(rename(serialize = "tmodloader_version"))] pub modloaderversion: String,
-	pub modreferences: String,
+	#[serde(deserialize_with = "deserialize_mod_references")] pub modreferences: Vec<ModReference>,
 	pub modside: ModSide,
 	pub description: Option<String>,
 	pub homepage: Option<String>,
 	pub icon: Option<String>
 }
 
+// modinfo.php sends the references as a string, mod info which was serialized by us has them parsed already
+fn deserialize_mod_references<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<ModReference>, D::Error> {
+	#[derive(Deserialize)]
+	#[serde(crate = "rocket::serde", untagged)]
+	enum References {
+		Raw(String),
+		Parsed(Vec<ModReference>)
+	}
+
+	Ok(match References::deserialize(deserializer)? {
+		References::Raw(references) => parse::parse_mod_references(&references),
+		References::Parsed(references) => references
+	})
+}
+
 #[derive(Deserialize)]
 #[serde(crate = "rocket::serde")]
 pub struct DescriptionResponse {
@@ -36,7 +47,7 @@ pub struct DescriptionResponse {
 	pub homepage: String
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
 #[serde(crate = "rocket::serde")]
 pub struct AuthorModInfo {
 	pub rank: u32,
@@ -45,7 +56,7 @@ pub struct AuthorModInfo {
 	pub downloads_yesterday: u32
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
 #[serde(crate = "rocket::serde")]
 pub struct MaintainedModInfo {
 	pub internal_name: String,
@@ -53,8 +64,9 @@ pub struct MaintainedModInfo {
 	pub downloads_yesterday: u32
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
 #[serde(crate = "rocket::serde")]
+#[schemars(rename = "AuthorInfo_1_3")]
 pub struct AuthorInfo {
 	pub steam_id: String,
 	pub steam_name: String,
@@ -66,24 +78,39 @@ pub struct AuthorInfo {
 	pub maintained_mods: Vec<MaintainedModInfo>
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Clone, JsonSchema)]
 #[serde(crate = "rocket::serde")]
 pub struct ModListInfo {
 	pub rank: u32,
-	pub internal_name: String,
+	// null if the mod couldn't be matched to a row of modmigrationprogress.php or the archive
+	pub internal_name: Option<String>,
 	pub display_name: String,
 	pub downloads_total: u32,
 	pub downloads_today: u32,
 	pub downloads_yesterday: u32,
 	pub mod_version: String,
-	pub tmodloader_version: String
+	pub tmodloader_version: String,
+	pub merge_status: MergeStatus
+}
+
+// where the internal name and downloads_today of a mod list entry came from
+#[derive(Serialize, Clone, Copy, PartialEq, Debug, JsonSchema)]
+#[serde(crate = "rocket::serde", rename_all = "snake_case")]
+pub enum MergeStatus {
+	// found on modmigrationprogress.php
+	Matched,
+	// not downloaded today, the internal name is from the archive
+	Archived,
+	Unmatched
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, JsonSchema)]
 #[serde(crate = "rocket::serde")]
 pub struct ModHistory {
 	pub version: String,
 	pub downloads_total: u32,
 	pub tmodloader_version: String,
-	pub publish_date: String,
+	pub publish_date: DateTime<Utc>,
+	// downloads compared to the version published before it, null for the first version
+	pub downloads_delta: Option<i64>,
 }
\ No newline at end of file
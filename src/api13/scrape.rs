@@ -0,0 +1,137 @@
+use std::fmt::Display;
+use std::str::FromStr;
+use scraper::{ElementRef, Html, Selector};
+use crate::APIError;
+
+// where scraping a page of the 1.3 server failed
+#[derive(Debug)]
+pub struct ScrapeError {
+	page: &'static str,
+	table: &'static str,
+	// the first row after the header is 1
+	row: Option<usize>,
+	column: Option<&'static str>,
+	reason: String,
+}
+
+impl Display for ScrapeError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}: table '{}'", self.page, self.table)?;
+		if let Some(row) = self.row {
+			write!(f, ", row {row}")?;
+		}
+		if let Some(column) = self.column {
+			write!(f, ", column '{column}'")?;
+		}
+		write!(f, ": {}", self.reason)
+	}
+}
+
+impl From<ScrapeError> for APIError {
+	fn from(e: ScrapeError) -> Self {
+		log::warn!("{e}");
+		APIError::ScrapeError(e.to_string())
+	}
+}
+
+// the rows of a table on a scraped page, without the header row
+pub struct Table<'a> {
+	page: &'static str,
+	name: &'static str,
+	rows: Vec<ElementRef<'a>>,
+}
+
+impl<'a> Table<'a> {
+	// the rows of every table on the page, for pages with a single table
+	pub fn all(html: &'a Html, page: &'static str, name: &'static str) -> Result<Table<'a>, ScrapeError> {
+		Self::rows_of(html.root_element(), page, name)
+	}
+
+	// the n-th table on the page, starting at 0
+	pub fn nth(html: &'a Html, page: &'static str, name: &'static str, n: usize) -> Result<Table<'a>, ScrapeError> {
+		let tables: Vec<ElementRef> = html.select(&parse_selector("table", page, name)?).collect();
+		let table = tables.get(n).ok_or_else(|| ScrapeError {
+			page, table: name, row: None, column: None,
+			reason: format!("table {} is missing, the page has {} tables", n + 1, tables.len())
+		})?;
+		Self::rows_of(*table, page, name)
+	}
+
+	pub fn last(html: &'a Html, page: &'static str, name: &'static str) -> Result<Table<'a>, ScrapeError> {
+		let table = html.select(&parse_selector("table", page, name)?).next_back().ok_or_else(|| ScrapeError {
+			page, table: name, row: None, column: None,
+			reason: "the page has no tables".to_owned()
+		})?;
+		Self::rows_of(table, page, name)
+	}
+
+	fn rows_of(root: ElementRef<'a>, page: &'static str, name: &'static str) -> Result<Table<'a>, ScrapeError> {
+		let rows = parse_selector("tbody > tr:not(:first-child)", page, name)?;
+		Ok(Table { page, name, rows: root.select(&rows).collect() })
+	}
+
+	pub fn row_count(&self) -> usize {
+		self.rows.len()
+	}
+
+	// malformed rows are logged and skipped. If none of the rows could be parsed the page
+	// probably changed, then the error of the first row is returned
+	pub fn parse_rows<T>(&self, parse: impl Fn(&Row) -> Result<T, ScrapeError>) -> Result<Vec<T>, ScrapeError> {
+		let cell = parse_selector("td", self.page, self.name)?;
+		let mut parsed = Vec::with_capacity(self.rows.len());
+		let mut first_error = None;
+
+		for (i, row) in self.rows.iter().enumerate() {
+			let row = Row { page: self.page, table: self.name, index: i + 1, cells: row.select(&cell).collect() };
+			match parse(&row) {
+				Ok(value) => parsed.push(value),
+				Err(e) => {
+					log::warn!("Skipping malformed row: {e}");
+					first_error.get_or_insert(e);
+				}
+			}
+		}
+
+		match first_error {
+			Some(e) if parsed.is_empty() => Err(e),
+			_ => Ok(parsed)
+		}
+	}
+}
+
+// the cells of a table row
+pub struct Row<'a> {
+	page: &'static str,
+	table: &'static str,
+	index: usize,
+	cells: Vec<ElementRef<'a>>,
+}
+
+impl Row<'_> {
+	fn error(&self, column: &'static str, reason: String) -> ScrapeError {
+		ScrapeError { page: self.page, table: self.table, row: Some(self.index), column: Some(column), reason }
+	}
+
+	// the html inside the cell, columns start at 0
+	pub fn text(&self, column: usize, name: &'static str) -> Result<String, ScrapeError> {
+		self.cells.get(column)
+			.map(|cell| cell.inner_html())
+			.ok_or_else(|| self.error(name, format!("the row only has {} columns", self.cells.len())))
+	}
+
+	pub fn parse<T: FromStr>(&self, column: usize, name: &'static str) -> Result<T, ScrapeError> where T::Err: Display {
+		self.parse_with(column, name, str::parse)
+	}
+
+	pub fn parse_with<T, E: Display>(&self, column: usize, name: &'static str, parse: impl FnOnce(&str) -> Result<T, E>) -> Result<T, ScrapeError> {
+		let text = self.text(column, name)?;
+		parse(text.trim()).map_err(|e| self.error(name, format!("could not parse '{text}': {e}")))
+	}
+}
+
+fn parse_selector(selector: &str, page: &'static str, table: &'static str) -> Result<Selector, ScrapeError> {
+	Selector::parse(selector).map_err(|e| ScrapeError {
+		page, table, row: None, column: None,
+		reason: format!("invalid selector '{selector}': {e}")
+	})
+}
@@ -3,11 +3,13 @@ use rocket::response::content::RawHtml;
 use rocket::serde::json::serde_json::{self, Value};
 use rocket_cache_response::CacheResponse;
 use std::collections::HashMap;
+use std::sync::Arc;
+use sqlx::PgPool;
 
-use crate::api14::mod_api::get_filtered_mod_info;
-use crate::{api14::Api14State, cache, cached_json, steamapi};
-use crate::api_error::APIError;
-use crate::api14::responses::{AuthorInfo, ModInfo};
+use crate::{api14::{db, Api14State}, cached_json, stale_json, steamapi};
+use crate::api_error::{APIError, ErrorResponse};
+use crate::cache::Stale;
+use crate::api14::responses::{AuthorInfo, AuthorName, AuthorProfile, AvatarEntry, CreditedMod, CreditedName, SteamAvatar};
 
 #[get("/author")]
 pub fn index_author_1_4() -> RawHtml<&'static str> {
@@ -24,64 +26,239 @@ pub fn index_author_1_4() -> RawHtml<&'static str> {
 	"#)
 }
 
-async fn get_author_info(steamid: u64, state: &State<Api14State>) -> Result<CacheResponse<Value>, APIError> {
-	let author = match cache::lock_and_get(&state.author_cache, steamid, 3600) {
-		Some(cached_value) => cached_value,
-		None => {
-			let steam_user = steamapi::get_user_info(steamid, &state.steam_api_key).await?;
-			let author_data = steamapi::get_user_mods(steamid, &state.steam_api_key).await?;
-
-			let mut mods: Vec<ModInfo> = Vec::new();
-			let mut total_downloads: u64 = 0;
-			let mut total_favorites: u64 = 0;
-			let mut total_views: u64 = 0;
-
-			// go through each mod
-			for publishedfiledetail in author_data.publishedfiledetails.unwrap_or_default() {
-				// increment total counts
-				total_downloads += publishedfiledetail.subscriptions.unwrap_or_default() as u64;
-				total_favorites += publishedfiledetail.favorited.unwrap_or_default() as u64;
-				total_views += publishedfiledetail.views.unwrap_or_default() as u64;
-
-				// filter mod data and add to Vec
-				mods.push(get_filtered_mod_info(&publishedfiledetail));
-			}
+// authors that aren't in the last sync yet get their profile fetched from steam once
+async fn get_steam_profile(steamid: u64, steam_api_key: &str, db: &PgPool) -> Result<db::AuthorsRow, APIError> {
+	if let Some(profile) = db::get_author(steamid, db).await? {
+		return Ok(profile);
+	}
 
-			let author = AuthorInfo {
-				steam_id: steamid.to_string(),
-				steam_name: steam_user.personaname,
-				steam_avatar: steam_user.avatarfull,
-				mods,
-				total: author_data.total,
-				total_downloads,
-				total_favorites,
-				total_views,
-			};
-
-			cache::lock_and_update(&state.author_cache, steamid, author)
-		}
-	};
+	let steam_user = steamapi::get_user_info(steamid, steam_api_key).await?;
+	db::upsert_authors(&[steam_user], db).await?;
+	db::get_author(steamid, db).await?.ok_or(APIError::SteamIDNotFound(steamid))
+}
 
-	return cached_json!(author, 3600, false);
+async fn fetch_author_info(steamid: u64, steam_api_key: Arc<String>, db: Arc<PgPool>) -> Result<AuthorInfo, APIError> {
+	let profile = get_steam_profile(steamid, &steam_api_key, &db).await?;
+	let mods = db::get_mods(Some(steamid), &db).await?;
+
+	Ok(AuthorInfo {
+		steam_id: steamid.to_string(),
+		steam_name: profile.persona_name,
+		steam_avatar: profile.avatar_full,
+		country_code: profile.country_code,
+		total: mods.len() as u32,
+		total_downloads: mods.iter().map(|m| m.downloads_total as u64).sum(),
+		total_favorites: mods.iter().map(|m| m.favorited as u64).sum(),
+		total_views: mods.iter().map(|m| m.views).sum(),
+		mods,
+	})
+}
+
+async fn get_author_info(steamid: u64, state: &State<Api14State>) -> Result<Stale<CacheResponse<Value>>, APIError> {
+	let (steam_api_key, db) = (Arc::clone(&state.steam_api_key), Arc::clone(&state.db));
+	let (author, stale_for) = state.author_cache
+		.get_or_revalidate_with(steamid, move || fetch_author_info(steamid, steam_api_key, db))
+		.await?;
+
+	stale_json!(author, stale_for, 3600, false)
 }
 
 #[get("/author/<steamid>", rank=1)]
-pub async fn author_1_4(steamid: u64, state: &State<Api14State>) -> Result<CacheResponse<Value>, APIError> {
+pub async fn author_1_4(steamid: u64, state: &State<Api14State>) -> Result<Stale<CacheResponse<Value>>, APIError> {
 	steamapi::validate_steamid64(steamid)?;
 	get_author_info(steamid, state).await
 }
 
 #[get("/author/<steamname>", rank=2)]
-pub async fn author_1_4_str(steamname: &str, state: &State<Api14State>) -> Result<CacheResponse<Value>, APIError> {
-	let steamid = steamapi::steamname_to_steamid(steamname, &state.steam_api_key).await?;
+pub async fn author_1_4_str(steamname: &str, state: &State<Api14State>) -> Result<Stale<CacheResponse<Value>>, APIError> {
+	let steamid = state.name_cache.steam_id(steamname, &state.steam_api_key).await?;
 	get_author_info(steamid, state).await
 }
 
 
+// the 'Author' kvtag is free-form, teams usually list their members like "A, B & C" or "A and B"
+const AUTHOR_SEPARATORS: &[char] = &[',', '&', '/', '+', ';', '|'];
+
+pub fn split_author_names(author: &str) -> Vec<String> {
+	// replace the word "and" with a separator; ascii lowercasing keeps the byte offsets intact
+	let lowercase = author.to_ascii_lowercase();
+	let mut normalized = String::with_capacity(author.len());
+	let mut rest = 0;
+	for (i, _) in lowercase.match_indices(" and ") {
+		if i >= rest {
+			normalized.push_str(&author[rest..i]);
+			normalized.push(',');
+			rest = i + " and ".len();
+		}
+	}
+	normalized.push_str(&author[rest..]);
+
+	let mut names: Vec<String> = Vec::new();
+	for name in normalized.split(AUTHOR_SEPARATORS).map(str::trim).filter(|n| !n.is_empty()) {
+		if !names.iter().any(|n| n.eq_ignore_ascii_case(name)) {
+			names.push(name.to_owned());
+		}
+	}
+
+	names
+}
+
+async fn get_author_profile(steamid: u64, state: &State<Api14State>) -> Result<CacheResponse<Value>, APIError> {
+	let steam_profile = get_steam_profile(steamid, &state.steam_api_key, &state.db).await?;
+	let display_names = db::get_author_names(steamid, &state.db).await?;
+	let (first_seen, last_seen) = db::get_author_activity(steamid, &state.db).await?;
+
+	// names used on their own or matching the steam name belong to the author, everyone else is a co-author
+	let mut own_names: Vec<String> = vec![steam_profile.persona_name.clone()];
+	let mut credited_names: Vec<String> = Vec::new();
+	for display_name in &display_names {
+		let names = split_author_names(&display_name.name);
+		if let [name] = names.as_slice() {
+			own_names.push(name.clone());
+		}
+		for name in names {
+			if !credited_names.iter().any(|n| n.eq_ignore_ascii_case(&name)) {
+				credited_names.push(name);
+			}
+		}
+	}
+
+	let credited_mods: Vec<(Vec<String>, CreditedMod)> = db::get_credited_mods(steamid, &credited_names, &state.db).await?
+		.into_iter()
+		.map(|row| (split_author_names(&row.author), CreditedMod {
+			mod_id: row.mod_id as u64,
+			display_name: row.display_name,
+			internal_name: row.internal_name,
+			uploader_id: row.author_id.to_string(),
+		}))
+		.collect();
+
+	let (names, co_authors): (Vec<CreditedName>, Vec<CreditedName>) = credited_names.into_iter()
+		.map(|name| CreditedName {
+			mods: credited_mods.iter()
+				.filter(|(authors, _)| authors.iter().any(|a| a.eq_ignore_ascii_case(&name)))
+				.map(|(_, m)| m.clone())
+				.collect(),
+			name,
+		})
+		.partition(|credited| own_names.iter().any(|n| n.eq_ignore_ascii_case(&credited.name)));
+
+	let profile = AuthorProfile {
+		steam_id: steamid.to_string(),
+		steam_name: steam_profile.persona_name,
+		display_names: display_names.into_iter()
+			.map(|n| AuthorName { name: n.name, first_seen: n.first_seen, last_seen: n.last_seen })
+			.collect(),
+		first_seen,
+		last_seen,
+		names,
+		co_authors,
+	};
+
+	cached_json!(profile, 3600, false)
+}
+
+#[get("/author/<steamid>/profile", rank=1)]
+pub async fn author_profile_1_4(steamid: u64, state: &State<Api14State>) -> Result<CacheResponse<Value>, APIError> {
+	steamapi::validate_steamid64(steamid)?;
+	get_author_profile(steamid, state).await
+}
+
+#[get("/author/<steamname>/profile", rank=2)]
+pub async fn author_profile_1_4_str(steamname: &str, state: &State<Api14State>) -> Result<CacheResponse<Value>, APIError> {
+	let steamid = state.name_cache.steam_id(steamname, &state.steam_api_key).await?;
+	get_author_profile(steamid, state).await
+}
+
 #[get("/get_steam_avatar?<steamids>")]
 pub async fn get_steam_avatar(steamids: Vec<u64>, state: &State<Api14State>) -> Result<Value, APIError> {
-	let user_infos = steamapi::get_users_info(steamids.as_slice(), &state.steam_api_key).await?;
-	let s: HashMap<String, String> = user_infos.iter().map(|f| (f.steamid.clone(), f.avatarfull.clone())).collect();
+	let mut avatars: HashMap<String, AvatarEntry> = HashMap::with_capacity(steamids.len());
+	let mut uncached: Vec<u64> = Vec::new();
+
+	for steamid in steamids {
+		if let Err(e) = steamapi::validate_steamid64(steamid) {
+			avatars.insert(steamid.to_string(), AvatarEntry::Error(ErrorResponse::from(&e)));
+			continue;
+		}
+
+		match state.avatar_cache.get(&steamid).await {
+			Some(avatar) => { avatars.insert(steamid.to_string(), avatar_entry(steamid, avatar)); },
+			None => uncached.push(steamid)
+		}
+	}
+
+	uncached.sort_unstable();
+	uncached.dedup();
+
+	for chunk in uncached.chunks(steamapi::MAX_STEAMIDS_PER_REQUEST) {
+		// a failed request only fails its own steamids, which aren't cached so they're requested again next time
+		let user_infos = match steamapi::get_users_info(chunk, &state.steam_api_key).await {
+			Ok(user_infos) => user_infos,
+			Err(e) => {
+				for &steamid in chunk {
+					avatars.insert(steamid.to_string(), AvatarEntry::Error(ErrorResponse::from(&e)));
+				}
+				continue;
+			}
+		};
+
+		for &steamid in chunk {
+			// unknown steamids are cached as well so they don't get requested again right away
+			let avatar = user_infos.iter()
+				.find(|user| user.steamid == steamid.to_string())
+				.map(SteamAvatar::from);
 
-	return Ok(serde_json::json!(s));
-}
\ No newline at end of file
+			state.avatar_cache.insert(steamid, avatar.clone()).await;
+			avatars.insert(steamid.to_string(), avatar_entry(steamid, avatar));
+		}
+	}
+
+	Ok(serde_json::json!(avatars))
+}
+
+fn avatar_entry(steamid: u64, avatar: Option<SteamAvatar>) -> AvatarEntry {
+	match avatar {
+		Some(avatar) => AvatarEntry::Found(avatar),
+		None => AvatarEntry::Error(ErrorResponse::from(&APIError::SteamIDNotFound(steamid)))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn single_name() {
+		assert_eq!(split_author_names("  Foo  "), ["Foo"]);
+	}
+
+	#[test]
+	fn separators() {
+		assert_eq!(split_author_names("A, B & C"), ["A", "B", "C"]);
+		assert_eq!(split_author_names("A/B+C;D|E"), ["A", "B", "C", "D", "E"]);
+	}
+
+	#[test]
+	fn and() {
+		assert_eq!(split_author_names("A and B"), ["A", "B"]);
+		assert_eq!(split_author_names("A And B, C AND D"), ["A", "B", "C", "D"]);
+		// only the word, not names containing it
+		assert_eq!(split_author_names("Andrew and Sandy"), ["Andrew", "Sandy"]);
+		assert_eq!(split_author_names("Brand"), ["Brand"]);
+	}
+
+	#[test]
+	fn empty_and_repeated_parts() {
+		assert!(split_author_names("").is_empty());
+		assert!(split_author_names(" , & ").is_empty());
+		assert_eq!(split_author_names(",A,,B,"), ["A", "B"]);
+		// the first spelling is kept
+		assert_eq!(split_author_names("Foo, foo & FOO and Bar"), ["Foo", "Bar"]);
+	}
+
+	#[test]
+	fn non_ascii_names() {
+		assert_eq!(split_author_names("Łukasz & Zoë"), ["Łukasz", "Zoë"]);
+		assert_eq!(split_author_names("Ärger and Ödön, 星"), ["Ärger", "Ödön", "星"]);
+	}
+}
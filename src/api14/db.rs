@@ -1,10 +1,13 @@
 use crate::{
 	api_error::APIError,
-	api14::{mod_api, responses::ModInfo},
+	api14::{mod_api, responses::{ModInfo, ModSocials, ModVersion}},
+	steamapi,
 };
-use chrono::{Timelike, Utc};
+use chrono::{DateTime, NaiveDate, Timelike, Utc};
+use rocket::futures::stream::BoxStream;
 use rocket::serde::Serialize;
 use sqlx::{PgPool, Postgres, Transaction, postgres::PgPoolOptions};
+use std::collections::HashMap;
 use std::time::Duration;
 
 #[derive(Debug, Serialize, sqlx::FromRow)]
@@ -39,6 +42,55 @@ pub struct ModsRow {
 	pub sketchfab: Option<String>
 }
 
+#[derive(Debug, Serialize, sqlx::FromRow)]
+#[serde(crate = "rocket::serde")]
+pub struct ModHistoryRow {
+	pub date: NaiveDate,
+	pub mod_id: i64,
+	pub author_id: Option<i64>,
+	pub downloads_total: Option<i32>,
+	pub views: Option<i64>,
+	pub followers: Option<i32>,
+	pub favorited: Option<i32>,
+	pub votes_up: Option<i32>,
+	pub votes_down: Option<i32>,
+	pub score: Option<f64>,
+	pub num_comments: Option<i32>,
+	pub playtime: Option<i64>,
+	pub time_updated: Option<i64>,
+	pub version: Option<String>
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+#[serde(crate = "rocket::serde")]
+pub struct AuthorsRow {
+	pub author_id: i64,
+	pub persona_name: String,
+	pub profile_url: String,
+	pub avatar: String,
+	pub avatar_medium: String,
+	pub avatar_full: String,
+	pub country_code: Option<String>,
+	pub updated_at: DateTime<Utc>
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+#[serde(crate = "rocket::serde")]
+pub struct AuthorNamesRow {
+	pub name: String,
+	pub first_seen: NaiveDate,
+	pub last_seen: NaiveDate
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct CreditedModsRow {
+	pub mod_id: i64,
+	pub display_name: String,
+	pub internal_name: String,
+	pub author: String,
+	pub author_id: i64
+}
+
 pub async fn create_pool() -> PgPool {
 	PgPoolOptions::new()
 		.max_connections(10)
@@ -56,8 +108,17 @@ pub async fn update_db(db: &PgPool, steam_api_key: &str) -> Result<(), APIError>
 
 	let mods = mod_api::get_filtered_mod_list(steam_api_key).await?;
 	update_mod_list(&mods, db).await?;
+	update_author_names(db).await?;
+	record_sync(SyncedTable::Mods, db).await?;
 	log::info!("Finished updating mods table");
 
+	// a failed profile refresh shouldn't keep mod_history from being updated
+	log::info!("Updating authors table");
+	match update_authors(&mods, db, steam_api_key).await {
+		Ok(()) => log::info!("Finished updating authors table"),
+		Err(e) => log::error!("Could not update authors table: {e}")
+	}
+
 	// update mod_history table at 10:00UTC and retry every hour if it didn't work
 	if hour >= 10 {
 		if !check_if_updated(db).await? {
@@ -66,6 +127,7 @@ pub async fn update_db(db: &PgPool, steam_api_key: &str) -> Result<(), APIError>
 			}
 			log::info!("Updating mod_history table");
 			update_mod_history(&mods, db).await?;
+			record_sync(SyncedTable::ModHistory, db).await?;
 			log::info!("Finished updating mod_history table");
 		} else {
 			log::info!("mod_history already updated")
@@ -74,6 +136,41 @@ pub async fn update_db(db: &PgPool, steam_api_key: &str) -> Result<(), APIError>
 	Ok(())
 }
 
+#[derive(Clone, Copy)]
+pub enum SyncedTable {
+	Mods,
+	ModHistory
+}
+
+impl SyncedTable {
+	fn name(self) -> &'static str {
+		match self {
+			SyncedTable::Mods => "mods",
+			SyncedTable::ModHistory => "mod_history"
+		}
+	}
+}
+
+pub async fn record_sync(table: SyncedTable, db: &PgPool) -> Result<(), APIError> {
+	sqlx::query!(
+		r#"
+		INSERT INTO syncs (name, synced_at) VALUES ($1, NOW())
+		ON CONFLICT (name) DO UPDATE SET synced_at = EXCLUDED.synced_at
+		"#,
+		table.name()
+	).execute(db).await?;
+	Ok(())
+}
+
+// returns None if the table hasn't been synced since the syncs table exists
+pub async fn last_sync(table: SyncedTable, db: &PgPool) -> Result<Option<DateTime<Utc>>, APIError> {
+	let synced_at = sqlx::query_scalar!(
+		"SELECT synced_at FROM syncs WHERE name = $1",
+		table.name()
+	).fetch_optional(db).await?;
+	Ok(synced_at)
+}
+
 // returns true if the number of mods in the mod_history table for the current day is above zero
 pub async fn check_if_updated(db: &PgPool) -> Result<bool, APIError> {
 	let cnt: Option<i64> = sqlx::query_scalar!(
@@ -422,3 +519,526 @@ pub async fn update_mod_list(mods: &Vec<ModInfo>, db: &PgPool) -> Result<(), API
 
 	Ok(())
 }
+
+// refreshes the steam profiles of every author in the mod list, 100 steamids per request
+pub async fn update_authors(mods: &[ModInfo], db: &PgPool, steam_api_key: &str) -> Result<(), APIError> {
+	let mut author_ids: Vec<u64> = mods.iter()
+		.filter_map(|m| m.author_id.parse().ok())
+		.collect();
+	author_ids.sort_unstable();
+	author_ids.dedup();
+
+	for chunk in author_ids.chunks(steamapi::MAX_STEAMIDS_PER_REQUEST) {
+		let users = steamapi::get_users_info(chunk, steam_api_key).await?;
+		upsert_authors(&users, db).await?;
+	}
+
+	Ok(())
+}
+
+pub async fn upsert_authors(users: &[steamapi::SteamUserInfo], db: &PgPool) -> Result<(), APIError> {
+	if users.is_empty() {
+		return Ok(());
+	}
+
+	let len = users.len();
+	let mut author_ids = Vec::with_capacity(len);
+	let mut persona_names = Vec::with_capacity(len);
+	let mut profile_urls = Vec::with_capacity(len);
+	let mut avatars = Vec::with_capacity(len);
+	let mut avatars_medium = Vec::with_capacity(len);
+	let mut avatars_full = Vec::with_capacity(len);
+	let mut country_codes = Vec::with_capacity(len);
+
+	for user in users {
+		let Ok(author_id) = user.steamid.parse::<i64>() else {
+			log::warn!("Steam returned an invalid steamid: {}", user.steamid);
+			continue;
+		};
+
+		author_ids.push(author_id);
+		persona_names.push(user.personaname.clone());
+		profile_urls.push(user.profileurl.clone());
+		avatars.push(user.avatar.clone());
+		avatars_medium.push(user.avatarmedium.clone());
+		avatars_full.push(user.avatarfull.clone());
+		country_codes.push(user.loccountrycode.clone());
+	}
+
+	sqlx::query!(
+		r#"
+		INSERT INTO authors (
+			author_id, persona_name, profile_url,
+			avatar, avatar_medium, avatar_full, country_code
+		)
+		SELECT *
+		FROM UNNEST(
+			$1::BIGINT[], $2::TEXT[], $3::TEXT[],
+			$4::TEXT[], $5::TEXT[], $6::TEXT[], $7::TEXT[]
+		)
+		ON CONFLICT (author_id) DO UPDATE SET
+			persona_name = EXCLUDED.persona_name,
+			profile_url = EXCLUDED.profile_url,
+			avatar = EXCLUDED.avatar,
+			avatar_medium = EXCLUDED.avatar_medium,
+			avatar_full = EXCLUDED.avatar_full,
+			country_code = EXCLUDED.country_code,
+			updated_at = NOW()
+		"#,
+		&author_ids,
+		&persona_names,
+		&profile_urls,
+		&avatars,
+		&avatars_medium,
+		&avatars_full,
+		&country_codes as &[Option<String>]
+	)
+	.execute(db)
+	.await?;
+
+	Ok(())
+}
+
+// remembers every 'Author' kvtag value in the mods table and when it was last used
+pub async fn update_author_names(db: &PgPool) -> Result<(), APIError> {
+	sqlx::query!(
+		r#"
+		INSERT INTO author_names (author_id, name, first_seen, last_seen)
+		SELECT DISTINCT author_id, author, CURRENT_DATE, CURRENT_DATE
+		FROM mods
+		ON CONFLICT (author_id, name) DO UPDATE SET
+			last_seen = CURRENT_DATE
+		"#
+	)
+	.execute(db)
+	.await?;
+
+	Ok(())
+}
+
+pub async fn get_author_names(author_id: u64, db: &PgPool) -> Result<Vec<AuthorNamesRow>, APIError> {
+	let names = sqlx::query_as!(AuthorNamesRow,
+		r#"
+		SELECT name, first_seen, last_seen
+		FROM author_names
+		WHERE author_id = $1
+		ORDER BY last_seen DESC, first_seen
+		"#,
+		author_id as i64
+	)
+	.fetch_all(db)
+	.await?;
+
+	Ok(names)
+}
+
+// returns the first and last day an author shows up in either the mod history or the name history
+pub async fn get_author_activity(author_id: u64, db: &PgPool) -> Result<(Option<NaiveDate>, Option<NaiveDate>), APIError> {
+	let row = sqlx::query!(
+		r#"
+		SELECT
+			LEAST(
+				(SELECT MIN(date) FROM mod_history WHERE author_id = $1),
+				(SELECT MIN(first_seen) FROM author_names WHERE author_id = $1)
+			) AS first_seen,
+			GREATEST(
+				(SELECT MAX(date) FROM mod_history WHERE author_id = $1),
+				(SELECT MAX(last_seen) FROM author_names WHERE author_id = $1)
+			) AS last_seen
+		"#,
+		author_id as i64
+	)
+	.fetch_one(db)
+	.await?;
+
+	Ok((row.first_seen, row.last_seen))
+}
+
+// an ILIKE pattern matching strings that contain the text, LIKE wildcards in it are escaped
+fn contains_pattern(text: &str) -> String {
+	format!("%{}%", text.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_"))
+}
+
+fn to_i64s(ids: &[u64]) -> Vec<i64> {
+	ids.iter().map(|&id| id as i64).collect()
+}
+
+// returns the mods uploaded by the author and every mod whose 'Author' field mentions one of the names
+pub async fn get_credited_mods(author_id: u64, names: &[String], db: &PgPool) -> Result<Vec<CreditedModsRow>, APIError> {
+	// the results get filtered exactly by the caller
+	let patterns: Vec<String> = names.iter().map(|name| contains_pattern(name)).collect();
+
+	let mods = sqlx::query_as!(CreditedModsRow,
+		r#"
+		SELECT mod_id, display_name, internal_name, author, author_id
+		FROM mods
+		WHERE author_id = $1 OR author ILIKE ANY($2)
+		ORDER BY downloads_total DESC
+		"#,
+		author_id as i64,
+		&patterns
+	)
+	.fetch_all(db)
+	.await?;
+
+	Ok(mods)
+}
+
+pub async fn get_author(author_id: u64, db: &PgPool) -> Result<Option<AuthorsRow>, APIError> {
+	let author = sqlx::query_as!(AuthorsRow,
+		r#"
+		SELECT * FROM authors
+		WHERE author_id = $1
+		"#,
+		author_id as i64
+	)
+	.fetch_optional(db)
+	.await?;
+
+	Ok(author)
+}
+
+pub async fn get_authors(author_ids: &[u64], db: &PgPool) -> Result<Vec<AuthorsRow>, APIError> {
+	let authors = sqlx::query_as!(AuthorsRow,
+		r#"
+		SELECT * FROM authors
+		WHERE author_id = ANY($1)
+		"#,
+		&to_i64s(author_ids)
+	)
+	.fetch_all(db)
+	.await?;
+
+	Ok(authors)
+}
+
+// (author id, mod id) of every mod uploaded by one of the authors
+pub async fn get_author_mod_ids(author_ids: &[u64], db: &PgPool) -> Result<Vec<(u64, u64)>, APIError> {
+	let rows = sqlx::query!(
+		r#"
+		SELECT author_id, mod_id
+		FROM mods
+		WHERE author_id = ANY($1)
+		"#,
+		&to_i64s(author_ids)
+	)
+	.fetch_all(db)
+	.await?;
+
+	Ok(rows.into_iter().map(|r| (r.author_id as u64, r.mod_id as u64)).collect())
+}
+
+// authors ordered by the downloads of all their mods
+pub async fn get_top_author_ids(limit: i64, offset: i64, db: &PgPool) -> Result<Vec<u64>, APIError> {
+	let rows = sqlx::query!(
+		r#"
+		SELECT author_id
+		FROM mods
+		GROUP BY author_id
+		ORDER BY SUM(downloads_total) DESC, author_id
+		LIMIT $1 OFFSET $2
+		"#,
+		limit,
+		offset
+	)
+	.fetch_all(db)
+	.await?;
+
+	Ok(rows.into_iter().map(|r| r.author_id as u64).collect())
+}
+
+// filters, order and page of a mod query. Without a sort column mods are ordered by id
+#[derive(Default)]
+pub struct ModFilter {
+	pub author_id: Option<u64>,
+	// tag or tag display name, case insensitive
+	pub tag: Option<String>,
+	// part of the display or internal name
+	pub search: Option<String>,
+	// the exact internal name
+	pub internal_name: Option<String>,
+	// only these mods
+	pub mod_ids: Option<Vec<u64>>,
+	// downloads, favorited, followers, views, score, created, updated or name
+	pub sort: Option<&'static str>,
+	pub descending: bool,
+	pub limit: Option<i64>,
+	pub offset: i64,
+}
+
+// assembles the mods (optionally only those of one author) from the mods tables
+pub async fn get_mods(author_id: Option<u64>, db: &PgPool) -> Result<Vec<ModInfo>, APIError> {
+	query_mods(&ModFilter { author_id, ..ModFilter::default() }, db).await
+}
+
+// number of mods matching the filter, ignoring limit and offset
+pub async fn count_mods(filter: &ModFilter, db: &PgPool) -> Result<i64, APIError> {
+	let filter_mod_ids = filter.mod_ids.as_deref().map(to_i64s);
+	let count = sqlx::query_scalar!(
+		r#"
+		SELECT COUNT(*) AS "count!"
+		FROM mods
+		WHERE ($1::BIGINT IS NULL OR author_id = $1)
+			AND ($2::TEXT IS NULL OR EXISTS (
+				SELECT 1 FROM mod_tags t
+				WHERE t.mod_id = mods.mod_id AND (lower(t.tag) = lower($2) OR lower(t.display_name) = lower($2))
+			))
+			AND ($3::TEXT IS NULL OR display_name ILIKE $3 OR internal_name ILIKE $3)
+			AND ($4::TEXT IS NULL OR internal_name = $4)
+			AND ($5::BIGINT[] IS NULL OR mod_id = ANY($5))
+		"#,
+		filter.author_id.map(|id| id as i64),
+		filter.tag.as_deref(),
+		filter.search.as_deref().map(contains_pattern),
+		filter.internal_name.as_deref(),
+		filter_mod_ids.as_deref()
+	)
+	.fetch_one(db)
+	.await?;
+
+	Ok(count)
+}
+
+pub async fn query_mods(filter: &ModFilter, db: &PgPool) -> Result<Vec<ModInfo>, APIError> {
+	let filter_mod_ids = filter.mod_ids.as_deref().map(to_i64s);
+	let mut tx: Transaction<Postgres> = db.begin().await?;
+
+	let rows: Vec<ModsRow> = sqlx::query_as!(ModsRow,
+		r#"
+		SELECT
+			mod_id AS "mod_id!", display_name AS "display_name!", internal_name AS "internal_name!",
+			author AS "author!", author_id AS "author_id!", modside AS "modside!", homepage AS "homepage!",
+			mod_references AS "mod_references!", num_versions AS "num_versions!",
+			time_created AS "time_created!", time_updated AS "time_updated!",
+			workshop_icon_url AS "workshop_icon_url!", description,
+			downloads_total AS "downloads_total!", favorited AS "favorited!", followers AS "followers!",
+			views AS "views!", playtime AS "playtime!", num_comments AS "num_comments!",
+			score AS "score!", votes_up AS "votes_up!", votes_down AS "votes_down!",
+			youtube, twitter, reddit, facebook, sketchfab
+		FROM mods
+		LEFT JOIN mod_socials USING (mod_id)
+		CROSS JOIN LATERAL (
+			SELECT CASE $4::TEXT
+				WHEN 'downloads' THEN downloads_total::FLOAT8
+				WHEN 'favorited' THEN favorited::FLOAT8
+				WHEN 'followers' THEN followers::FLOAT8
+				WHEN 'views' THEN views::FLOAT8
+				WHEN 'score' THEN score::FLOAT8
+				WHEN 'created' THEN time_created::FLOAT8
+				WHEN 'updated' THEN time_updated::FLOAT8
+			END AS sort_value
+		) sort
+		WHERE ($1::BIGINT IS NULL OR author_id = $1)
+			AND ($2::TEXT IS NULL OR EXISTS (
+				SELECT 1 FROM mod_tags t
+				WHERE t.mod_id = mods.mod_id AND (lower(t.tag) = lower($2) OR lower(t.display_name) = lower($2))
+			))
+			AND ($3::TEXT IS NULL OR display_name ILIKE $3 OR internal_name ILIKE $3)
+			AND ($8::TEXT IS NULL OR internal_name = $8)
+			AND ($9::BIGINT[] IS NULL OR mod_id = ANY($9))
+		ORDER BY
+			CASE WHEN $5 THEN sort.sort_value END DESC,
+			CASE WHEN NOT $5 THEN sort.sort_value END ASC,
+			CASE WHEN $4 = 'name' AND $5 THEN display_name END DESC,
+			CASE WHEN $4 = 'name' AND NOT $5 THEN display_name END ASC,
+			mod_id
+		LIMIT $6 OFFSET $7
+		"#,
+		filter.author_id.map(|id| id as i64),
+		filter.tag.as_deref(),
+		filter.search.as_deref().map(contains_pattern),
+		filter.sort,
+		filter.descending,
+		filter.limit,
+		filter.offset,
+		filter.internal_name.as_deref(),
+		filter_mod_ids.as_deref()
+	).fetch_all(&mut *tx).await?;
+
+	let mod_ids: Vec<i64> = rows.iter().map(|r| r.mod_id).collect();
+
+	// map mod ids to mod list of versions
+	let mut versions_map: HashMap<i64, Vec<ModVersion>> = sqlx::query!(
+		r#"
+		SELECT mod_id, mod_version, tmodloader_version
+		FROM mod_versions
+		WHERE mod_id = ANY($1)
+		ORDER BY mod_version
+		"#,
+		&mod_ids
+	)
+	.fetch_all(&mut *tx)
+	.await?
+	.into_iter()
+	.fold(HashMap::new(), |mut acc, row| {
+		acc.entry(row.mod_id)
+			.or_default()
+			.push(ModVersion {
+				mod_version: row.mod_version,
+				tmodloader_version: row.tmodloader_version,
+			});
+		acc
+	});
+
+	// mod mod ids to list of tags
+	let mut tags_map: HashMap<i64, Vec<steamapi::ModTag>> = sqlx::query!(
+		r#"
+		SELECT mod_id, tag, display_name
+		FROM mod_tags
+		WHERE mod_id = ANY($1)
+		"#,
+		&mod_ids
+	)
+	.fetch_all(&mut *tx)
+	.await?
+	.into_iter()
+	.fold(HashMap::new(), |mut acc, row| {
+		acc.entry(row.mod_id)
+			.or_default()
+			.push(steamapi::ModTag {
+				tag: row.tag,
+				display_name: row.display_name,
+			});
+		acc
+	});
+
+	// map mod ids to list of children
+	let mut children_map: HashMap<i64, Vec<u64>> = sqlx::query!(
+		r#"
+		SELECT parent_mod_id, child_mod_id
+		FROM mod_children
+		WHERE parent_mod_id = ANY($1)
+		"#,
+		&mod_ids
+	)
+	.fetch_all(&mut *tx).await?
+	.into_iter()
+	.fold(HashMap::new(), |mut acc, row| {
+		acc.entry(row.parent_mod_id)
+			.or_default()
+			.push(row.child_mod_id as u64);
+		acc
+	});
+
+	let mut mods = Vec::with_capacity(rows.len());
+
+	for row in rows {	
+		mods.push(ModInfo {
+			display_name: row.display_name,
+			internal_name: row.internal_name,
+			mod_id: row.mod_id as u64,
+			author: row.author,
+			author_id: row.author_id.to_string(),
+			modside: row.modside,
+			homepage: row.homepage,
+			versions: versions_map.remove(&row.mod_id).unwrap_or_default(),
+			tags: tags_map.remove(&row.mod_id),
+			children: children_map.remove(&row.mod_id),
+			socials: [&row.youtube, &row.twitter, &row.reddit, &row.facebook, &row.sketchfab]
+				.iter().any(|f| f.as_deref().is_some()).then_some(ModSocials {
+				youtube: row.youtube,
+				twitter: row.twitter,
+				reddit: row.reddit,
+				facebook: row.facebook,
+				sketchfab: row.sketchfab
+			}),
+			mod_references: row.mod_references,
+			num_versions: row.num_versions as u32,
+			time_created: row.time_created as u64,
+			time_updated: row.time_updated as u64,
+			workshop_icon_url: row.workshop_icon_url,
+			description: row.description,
+			downloads_total: row.downloads_total as u32,
+			favorited: row.favorited as u32,
+			followers: row.followers as u32,
+			views: row.views as u64,
+			playtime: row.playtime,
+			num_comments: row.num_comments as u32,
+
+			vote_data: Some(steamapi::VoteData {
+				score: row.score,
+				votes_up: row.votes_up as u32,
+				votes_down: row.votes_down as u32,
+			})
+		})
+	}
+
+	tx.commit().await?;
+
+	Ok(mods)
+}
+
+// streams every row of the mods table without loading all of them into memory
+pub fn stream_mods(db: &PgPool) -> BoxStream<'_, Result<ModsRow, sqlx::Error>> {
+	sqlx::query_as!(ModsRow,
+		r#"
+		SELECT
+			mod_id AS "mod_id!", display_name AS "display_name!", internal_name AS "internal_name!",
+			author AS "author!", author_id AS "author_id!", modside AS "modside!", homepage AS "homepage!",
+			mod_references AS "mod_references!", num_versions AS "num_versions!",
+			time_created AS "time_created!", time_updated AS "time_updated!",
+			workshop_icon_url AS "workshop_icon_url!", description,
+			downloads_total AS "downloads_total!", favorited AS "favorited!", followers AS "followers!",
+			views AS "views!", playtime AS "playtime!", num_comments AS "num_comments!",
+			score AS "score!", votes_up AS "votes_up!", votes_down AS "votes_down!",
+			youtube, twitter, reddit, facebook, sketchfab
+		FROM mods
+		LEFT JOIN mod_socials USING (mod_id)
+		ORDER BY mod_id
+		"#
+	).fetch(db)
+}
+
+// streams the mod_history rows between from and to, both inclusive and optional
+pub fn stream_mod_history(from: Option<NaiveDate>, to: Option<NaiveDate>, db: &PgPool) -> BoxStream<'_, Result<ModHistoryRow, sqlx::Error>> {
+	sqlx::query_as!(ModHistoryRow,
+		r#"
+		SELECT
+			date, mod_id, author_id, downloads_total, views, followers, favorited,
+			votes_up, votes_down, score, num_comments, playtime, time_updated, version
+		FROM mod_history
+		WHERE ($1::DATE IS NULL OR date >= $1) AND ($2::DATE IS NULL OR date <= $2)
+		ORDER BY date, mod_id
+		"#,
+		from,
+		to
+	).fetch(db)
+}
+
+// the mod_history rows of the mods between from and to (inclusive), newest first
+pub async fn get_mod_history(mod_ids: &[u64], from: Option<NaiveDate>, to: Option<NaiveDate>, db: &PgPool) -> Result<Vec<ModHistoryRow>, APIError> {
+	let rows = sqlx::query_as!(ModHistoryRow,
+		r#"
+		SELECT
+			date, mod_id, author_id, downloads_total, views, followers, favorited,
+			votes_up, votes_down, score, num_comments, playtime, time_updated, version
+		FROM mod_history
+		WHERE mod_id = ANY($1) AND ($2::DATE IS NULL OR date >= $2) AND ($3::DATE IS NULL OR date <= $3)
+		ORDER BY date DESC
+		"#,
+		&to_i64s(mod_ids),
+		from,
+		to
+	)
+	.fetch_all(db)
+	.await?;
+
+	Ok(rows)
+}
+
+// the first day of every month which has mod_history rows, and how many rows it has
+#[cfg(feature = "parquet")]
+pub async fn history_months(db: &PgPool) -> Result<Vec<(NaiveDate, i64)>, APIError> {
+	let rows = sqlx::query!(
+		r#"
+		SELECT date_trunc('month', date)::DATE AS "month!", COUNT(*) AS "rows!"
+		FROM mod_history
+		GROUP BY 1
+		ORDER BY 1
+		"#
+	)
+	.fetch_all(db)
+	.await?;
+
+	Ok(rows.into_iter().map(|r| (r.month, r.rows)).collect())
+}
@@ -0,0 +1,166 @@
+use std::sync::Arc;
+use chrono::NaiveDate;
+use rocket::{Request, Response, State};
+use rocket::futures::StreamExt;
+use rocket::futures::stream::BoxStream;
+use rocket::http::ContentType;
+use rocket::response::{self, Responder};
+use rocket::response::content::RawHtml;
+use rocket::response::stream::TextStream;
+use rocket::serde::Serialize;
+use rocket::serde::json::serde_json;
+use sqlx::PgPool;
+use crate::api_error::APIError;
+use super::{db, Api14State};
+
+#[get("/export")]
+pub fn index_export() -> RawHtml<&'static str> {
+	RawHtml(r#"
+		<h1>Data export</h1>
+		<a href="/1.4/export/mods.ndjson">mods.ndjson</a><br>
+		<a href="/1.4/export/mods.csv">mods.csv</a><br>
+		<a href="/1.4/export/history.ndjson">history.ndjson</a><br>
+		<a href="/1.4/export/history.csv">history.csv</a><br>
+		<br>
+		history can be limited to a date range with ?from=YYYY-MM-DD&amp;to=YYYY-MM-DD<br>
+		<br>
+		<a href="/1.4">go back</a>
+	"#)
+}
+
+#[derive(Clone, Copy)]
+pub enum ExportFormat {
+	Ndjson,
+	Csv
+}
+
+impl ExportFormat {
+	fn content_type(self) -> ContentType {
+		match self {
+			ExportFormat::Ndjson => ContentType::new("application", "x-ndjson"),
+			ExportFormat::Csv => ContentType::CSV
+		}
+	}
+
+	fn extension(self) -> &'static str {
+		match self {
+			ExportFormat::Ndjson => "ndjson",
+			ExportFormat::Csv => "csv"
+		}
+	}
+}
+
+// turns rows into lines of the export, a csv export starts with a header line
+struct RowWriter {
+	format: ExportFormat,
+	first_row: bool,
+}
+
+impl RowWriter {
+	fn new(format: ExportFormat) -> RowWriter {
+		RowWriter { format, first_row: true }
+	}
+
+	fn write<T: Serialize>(&mut self, row: &T) -> Result<String, String> {
+		match self.format {
+			ExportFormat::Ndjson => serde_json::to_string(row)
+				.map(|line| line + "\n")
+				.map_err(|e| e.to_string()),
+			ExportFormat::Csv => {
+				let mut writer = csv::WriterBuilder::new()
+					.has_headers(std::mem::take(&mut self.first_row))
+					.from_writer(Vec::new());
+				writer.serialize(row).map_err(|e| e.to_string())?;
+				let line = writer.into_inner().map_err(|e| e.to_string())?;
+				String::from_utf8(line).map_err(|e| e.to_string())
+			}
+		}
+	}
+}
+
+// an export which is downloaded as a file
+pub struct Export<B> {
+	body: B,
+	content_type: ContentType,
+	filename: String,
+}
+
+impl<B> Export<B> {
+	pub fn new(body: B, content_type: ContentType, filename: String) -> Export<B> {
+		Export { body, content_type, filename }
+	}
+
+	fn streamed(body: B, format: ExportFormat, name: &str) -> Export<B> {
+		Export::new(body, format.content_type(), format!("{name}.{}", format.extension()))
+	}
+}
+
+impl<'r, 'o: 'r, B: Responder<'r, 'o>> Responder<'r, 'o> for Export<B> {
+	fn respond_to(self, req: &'r Request<'_>) -> response::Result<'o> {
+		Response::build_from(self.body.respond_to(req)?)
+			.header(self.content_type)
+			.raw_header("Content-Disposition", format!("attachment; filename=\"{}\"", self.filename))
+			.ok()
+	}
+}
+
+// rows are written as soon as they arrive from the database. The status code is already sent by then,
+// so an error can only end the export early
+fn export_rows<T, F>(format: ExportFormat, db: Arc<PgPool>, rows: F) -> TextStream![String]
+	where T: Serialize + Send, F: for<'a> FnOnce(&'a PgPool) -> BoxStream<'a, Result<T, sqlx::Error>> + Send + 'static
+{
+	TextStream! {
+		let mut rows = rows(&db);
+		let mut writer = RowWriter::new(format);
+		while let Some(row) = rows.next().await {
+			let line = match row {
+				Ok(row) => writer.write(&row),
+				Err(e) => Err(e.to_string())
+			};
+
+			match line {
+				Ok(line) => yield line,
+				Err(e) => {
+					log::error!("Export stopped early: {e}");
+					break;
+				}
+			}
+		}
+	}
+}
+
+fn export_mods(format: ExportFormat, state: &State<Api14State>) -> Export<TextStream![String]> {
+	Export::streamed(export_rows(format, Arc::clone(&state.db), db::stream_mods), format, "mods")
+}
+
+fn parse_date(name: &str, date: Option<&str>) -> Result<Option<NaiveDate>, APIError> {
+	date.map(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d")
+		.map_err(|_| APIError::InvalidQuery(format!("'{name}': expected a date like 2024-01-31"))))
+		.transpose()
+}
+
+fn export_history(format: ExportFormat, from: Option<&str>, to: Option<&str>, state: &State<Api14State>) -> Result<Export<TextStream![String]>, APIError> {
+	let (from, to) = (parse_date("from", from)?, parse_date("to", to)?);
+	let rows = export_rows(format, Arc::clone(&state.db), move |db| db::stream_mod_history(from, to, db));
+	Ok(Export::streamed(rows, format, "history"))
+}
+
+#[get("/export/mods.ndjson")]
+pub fn export_mods_ndjson(state: &State<Api14State>) -> Export<TextStream![String]> {
+	export_mods(ExportFormat::Ndjson, state)
+}
+
+#[get("/export/mods.csv")]
+pub fn export_mods_csv(state: &State<Api14State>) -> Export<TextStream![String]> {
+	export_mods(ExportFormat::Csv, state)
+}
+
+#[get("/export/history.ndjson?<from>&<to>")]
+pub fn export_history_ndjson(from: Option<&str>, to: Option<&str>, state: &State<Api14State>) -> Result<Export<TextStream![String]>, APIError> {
+	export_history(ExportFormat::Ndjson, from, to, state)
+}
+
+#[get("/export/history.csv?<from>&<to>")]
+pub fn export_history_csv(from: Option<&str>, to: Option<&str>, state: &State<Api14State>) -> Result<Export<TextStream![String]>, APIError> {
+	export_history(ExportFormat::Csv, from, to, state)
+}
@@ -1,11 +1,14 @@
 use rocket::State;
 use rocket::response::content::RawHtml;
-use rocket::serde::json::serde_json::Value;
+use rocket::serde::json::serde_json::{self, Value};
+use rocket_cache_response::CacheResponse;
 use sqlx::PgPool;
+use sqlx::types::Json;
 use crate::api_error::APIError;
-use crate::steamapi;
+use crate::conditional::LastModified;
+use crate::{steamapi, synced_json};
 
-use super::Api14State;
+use super::{db::{self, SyncedTable}, responses::{GlobalHistoryEntry, ModHistoryEntry}, Api14State};
 
 
 #[get("/history")]
@@ -51,7 +54,7 @@ pub fn index_history_author() -> RawHtml<&'static str> {
 	"#)
 }
 
-async fn get_mod_history(modid: u64, db: &PgPool) -> Result<Value, APIError> {
+async fn get_mod_history(modid: u64, db: &PgPool) -> Result<Vec<ModHistoryEntry>, APIError> {
 	let row = sqlx::query!(
 		r#"
 		SELECT json_agg(
@@ -74,7 +77,7 @@ async fn get_mod_history(modid: u64, db: &PgPool) -> Result<Value, APIError> {
 				'version', version
 			)
 			ORDER BY date DESC
-		) AS "history: Value"
+		) AS "history: Json<Vec<ModHistoryEntry>>"
 		FROM mod_history
 		WHERE mod_id = $1
 		"#,
@@ -83,22 +86,26 @@ async fn get_mod_history(modid: u64, db: &PgPool) -> Result<Value, APIError> {
 	.fetch_one(db)
 	.await?;
 
-	return Ok(row.history.unwrap_or(Value::Array(vec![])));
+	Ok(row.history.map(|history| history.0).unwrap_or_default())
 }
 
 #[get("/history/mod/<modid>", rank=1)]
-pub async fn history_mod(modid: u64, state: &State<Api14State>) -> Result<Value, APIError> {
-	get_mod_history(modid, &state.db).await
+pub async fn history_mod(modid: u64, state: &State<Api14State>) -> Result<LastModified<CacheResponse<Value>>, APIError> {
+	let history = get_mod_history(modid, &state.db).await?;
+	let synced_at = db::last_sync(SyncedTable::ModHistory, &state.db).await?;
+	synced_json!(history, synced_at, 3600, true)
 }
 
 #[get("/history/mod/<modname>", rank=2)]
-pub async fn history_mod_str(modname: &str, state: &State<Api14State>) -> Result<Value, APIError> {
-	let mod_id = steamapi::modname_to_modid(modname, &state.steam_api_key).await?;
-	return get_mod_history(mod_id, &state.db).await;
+pub async fn history_mod_str(modname: &str, state: &State<Api14State>) -> Result<LastModified<CacheResponse<Value>>, APIError> {
+	let mod_id = state.name_cache.mod_id(modname, &state.steam_api_key).await?;
+	let history = get_mod_history(mod_id, &state.db).await?;
+	let synced_at = db::last_sync(SyncedTable::ModHistory, &state.db).await?;
+	synced_json!(history, synced_at, 3600, true)
 }
 
 #[get("/history/global")]
-pub async fn history_global(state: &State<Api14State>) -> Result<Value, APIError> {
+pub async fn history_global(state: &State<Api14State>) -> Result<LastModified<CacheResponse<Value>>, APIError> {
 	let db: &PgPool = &state.db;
 	let row = sqlx::query!(
 		r#"
@@ -110,7 +117,7 @@ pub async fn history_global(state: &State<Api14State>) -> Result<Value, APIError
 				'favorited_total', SUM(favorited),
 				'playtime_total', SUM(playtime),
 				'comments_total', SUM(num_comments) 
-			) AS "history: Value" 
+			) AS "history: Json<GlobalHistoryEntry>" 
 		from mod_history GROUP BY date
 		ORDER BY date DESC
 		"#
@@ -118,12 +125,12 @@ pub async fn history_global(state: &State<Api14State>) -> Result<Value, APIError
 	.fetch_all(db)
 	.await?;
 
-	return Ok(Value::Array(
-		row.into_iter().filter_map(|f| f.history).collect()
-	));
+	let history: Vec<GlobalHistoryEntry> = row.into_iter().filter_map(|f| f.history).map(|history| history.0).collect();
+	let synced_at = db::last_sync(SyncedTable::ModHistory, db).await?;
+	synced_json!(history, synced_at, 3600, true)
 }
 
-async fn get_author_history(steamid: u64, db: &PgPool) -> Result<Value, APIError> {
+async fn get_author_history(steamid: u64, db: &PgPool) -> Result<Vec<ModHistoryEntry>, APIError> {
 	let row = sqlx::query!(
 		r#"
 		SELECT json_agg(
@@ -146,7 +153,7 @@ async fn get_author_history(steamid: u64, db: &PgPool) -> Result<Value, APIError
 				'version', version
 			)
 			ORDER BY date DESC
-		) AS "history: Value"
+		) AS "history: Json<Vec<ModHistoryEntry>>"
 		FROM mod_history
 		WHERE author_id = $1
 		"#,
@@ -155,17 +162,21 @@ async fn get_author_history(steamid: u64, db: &PgPool) -> Result<Value, APIError
 	.fetch_one(db)
 	.await?;
 
-	return Ok(row.history.unwrap_or(Value::Array(vec![])));
+	Ok(row.history.map(|history| history.0).unwrap_or_default())
 }
 
 #[get("/history/author/<steamid>", rank=1)]
-pub async fn history_author(steamid: u64, state: &State<Api14State>) -> Result<Value, APIError> {
+pub async fn history_author(steamid: u64, state: &State<Api14State>) -> Result<LastModified<CacheResponse<Value>>, APIError> {
 	steamapi::validate_steamid64(steamid)?;
-	return get_author_history(steamid, &state.db).await;
+	let history = get_author_history(steamid, &state.db).await?;
+	let synced_at = db::last_sync(SyncedTable::ModHistory, &state.db).await?;
+	synced_json!(history, synced_at, 3600, true)
 }
 
 #[get("/history/author/<steamname>", rank=2)]
-pub async fn history_author_str(steamname: &str, state: &State<Api14State>) -> Result<Value, APIError> {
-	let steamid = steamapi::steamname_to_steamid(steamname, &state.steam_api_key).await?;
-	return get_author_history(steamid, &state.db).await;
+pub async fn history_author_str(steamname: &str, state: &State<Api14State>) -> Result<LastModified<CacheResponse<Value>>, APIError> {
+	let steamid = state.name_cache.steam_id(steamname, &state.steam_api_key).await?;
+	let history = get_author_history(steamid, &state.db).await?;
+	let synced_at = db::last_sync(SyncedTable::ModHistory, &state.db).await?;
+	synced_json!(history, synced_at, 3600, true)
 }
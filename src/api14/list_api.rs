@@ -1,144 +1,226 @@
 extern crate reqwest;
 
-use std::collections::{HashMap};
-
-use rocket::State;
+use std::sync::Arc;
+use chrono::{DateTime, Utc};
+use rocket::{tokio, Request, State};
+use rocket::http::Header;
+use rocket::form::{self, error::ErrorKind};
+use rocket::response::{self, Responder};
 use rocket::serde::json::serde_json::{self, Value};
-use sqlx::{PgPool, Postgres, Transaction};
-use crate::api14::db::ModsRow;
-use crate::{APIError, steamapi};
-use super::{responses::*, Api14State};
+use rocket::tokio::sync::Mutex;
+use async_graphql::Enum;
+use rocket_cache_response::CacheResponse;
+use schemars::JsonSchema;
+use sqlx::PgPool;
+use sqlx::types::Json;
+use crate::{APIError, synced_json};
+use crate::api_error::serialize_error;
+use crate::compression::{Precompressed, PrecompressedJson};
+use crate::conditional::LastModified;
+use super::{db::{self, ModFilter, SyncedTable}, responses::ListedAuthor, Api14State};
+
+struct Snapshot {
+	synced_at: Option<DateTime<Utc>>,
+	body: Arc<Precompressed>,
+}
 
+// the whole mod list is a few megabytes, so it's serialized and compressed once after every sync.
+// it's also rebuilt if another instance synced the mods table in the meantime
+pub struct ListSnapshot {
+	snapshot: Mutex<Option<Snapshot>>,
+}
 
-#[get("/list")]
-pub async fn list_1_4(state: &State<Api14State>) -> Result<Value, APIError> {
-	let db: &PgPool = &state.db;
-	
-	let mut tx: Transaction<Postgres> = db.begin().await?;
+impl ListSnapshot {
+	pub fn new() -> ListSnapshot {
+		ListSnapshot { snapshot: Mutex::new(None) }
+	}
 
-	// get all mods
-	let rows: Vec<ModsRow> = sqlx::query_as!(ModsRow,
-		r#"
-		SELECT * FROM mods 
-		LEFT JOIN mod_socials USING (mod_id)
-		"#
-	).fetch_all(&mut *tx).await?;
+	pub async fn rebuild(&self, db: &PgPool) -> Result<(), APIError> {
+		let mut snapshot = self.snapshot.lock().await;
+		*snapshot = Some(Self::build(db).await?);
+		Ok(())
+	}
 
-	let mod_ids: Vec<i64> = rows.iter().map(|r| r.mod_id).collect();
+	async fn get(&self, db: &PgPool) -> Result<(Arc<Precompressed>, Option<DateTime<Utc>>), APIError> {
+		let synced_at = db::last_sync(SyncedTable::Mods, db).await?;
 
-	// map mod ids to mod list of versions
-	let mut versions_map: HashMap<i64, Vec<ModVersion>> = sqlx::query!(
-		r#"
-		SELECT mod_id, mod_version, tmodloader_version
-		FROM mod_versions
-		WHERE mod_id = ANY($1)
-		ORDER BY mod_version
-		"#,
-		&mod_ids
-	)
-	.fetch_all(&mut *tx)
-	.await?
-	.into_iter()
-	.fold(HashMap::new(), |mut acc, row| {
-		acc.entry(row.mod_id)
-			.or_default()
-			.push(ModVersion {
-				mod_version: row.mod_version,
-				tmodloader_version: row.tmodloader_version,
-			});
-		acc
-	});
-
-	// mod mod ids to list of tags
-	let mut tags_map: HashMap<i64, Vec<steamapi::ModTag>> = sqlx::query!(
-		r#"
-		SELECT mod_id, tag, display_name
-		FROM mod_tags
-		WHERE mod_id = ANY($1)
-		"#,
-		&mod_ids
-	)
-	.fetch_all(&mut *tx)
-	.await?
-	.into_iter()
-	.fold(HashMap::new(), |mut acc, row| {
-		acc.entry(row.mod_id)
-			.or_default()
-			.push(steamapi::ModTag {
-				tag: row.tag,
-				display_name: row.display_name,
-			});
-		acc
-	});
-
-	// map mod ids to list of children
-	let mut children_map: HashMap<i64, Vec<u64>> = sqlx::query!(
-		r#"
-		SELECT parent_mod_id, child_mod_id
-		FROM mod_children
-		WHERE parent_mod_id = ANY($1)
-		"#,
-		&mod_ids
-	)
-	.fetch_all(&mut *tx).await?
-	.into_iter()
-	.fold(HashMap::new(), |mut acc, row| {
-		acc.entry(row.parent_mod_id)
-			.or_default()
-			.push(row.child_mod_id as u64);
-		acc
-	});
-
-	let mut mods = Vec::with_capacity(rows.len());
-
-	for row in rows {	
-		mods.push(ModInfo {
-			display_name: row.display_name,
-			internal_name: row.internal_name,
-			mod_id: row.mod_id as u64,
-			author: row.author,
-			author_id: row.author_id.to_string(),
-			modside: row.modside,
-			homepage: row.homepage,
-			versions: versions_map.remove(&row.mod_id).unwrap_or_default(),
-			tags: tags_map.remove(&row.mod_id),
-			children: children_map.remove(&row.mod_id),
-			socials: [&row.youtube, &row.twitter, &row.reddit, &row.facebook, &row.sketchfab]
-				.iter().any(|f| f.as_deref().is_some()).then_some(ModSocials {
-				youtube: row.youtube,
-				twitter: row.twitter,
-				reddit: row.reddit,
-				facebook: row.facebook,
-				sketchfab: row.sketchfab
-			}),
-			mod_references: row.mod_references,
-			num_versions: row.num_versions as u32,
-			time_created: row.time_created as u64,
-			time_updated: row.time_updated as u64,
-			workshop_icon_url: row.workshop_icon_url,
-			description: row.description,
-			downloads_total: row.downloads_total as u32,
-			favorited: row.favorited as u32,
-			followers: row.followers as u32,
-			views: row.views as u64,
-			playtime: row.playtime,
-			num_comments: row.num_comments as u32,
-
-			vote_data: Some(crate::steamapi::VoteData {
-				score: row.score,
-				votes_up: row.votes_up as u32,
-				votes_down: row.votes_down as u32,
-			})
-		})
+		let mut snapshot = self.snapshot.lock().await;
+		if let Some(snapshot) = snapshot.as_ref().filter(|snapshot| snapshot.synced_at == synced_at) {
+			return Ok((Arc::clone(&snapshot.body), snapshot.synced_at));
+		}
+
+		let built = Self::build(db).await?;
+		let result = (Arc::clone(&built.body), built.synced_at);
+		*snapshot = Some(built);
+		Ok(result)
 	}
 
-	tx.commit().await?;
+	async fn build(db: &PgPool) -> Result<Snapshot, APIError> {
+		let synced_at = db::last_sync(SyncedTable::Mods, db).await?;
+		let mods = db::get_mods(None, db).await?;
+		let json = serde_json::to_vec(&mods).map_err(serialize_error)?;
+		let body = tokio::task::spawn_blocking(move || Precompressed::new(json)).await
+			.map_err(|e| {
+				log::error!("Compressing the mod list failed: {e}");
+				APIError::SerializeError(format!("compressing the mod list failed: {e}"))
+			})?;
+
+		Ok(Snapshot { synced_at, body: Arc::new(body) })
+	}
+}
+
+#[derive(FromFormField, JsonSchema, Enum, Clone, Copy, PartialEq, Eq)]
+#[schemars(rename_all = "snake_case")]
+pub enum ModSort {
+	Downloads,
+	Favorited,
+	Followers,
+	Views,
+	Score,
+	Created,
+	Updated,
+	Name
+}
+
+impl ModSort {
+	pub fn key(self) -> &'static str {
+		match self {
+			ModSort::Downloads => "downloads",
+			ModSort::Favorited => "favorited",
+			ModSort::Followers => "followers",
+			ModSort::Views => "views",
+			ModSort::Score => "score",
+			ModSort::Created => "created",
+			ModSort::Updated => "updated",
+			ModSort::Name => "name"
+		}
+	}
+}
+
+#[derive(FromFormField, JsonSchema, Enum, Clone, Copy, PartialEq, Eq)]
+#[schemars(rename_all = "snake_case")]
+pub enum SortOrder {
+	Asc,
+	Desc
+}
+
+pub const DEFAULT_PAGE_SIZE: u32 = 100;
+pub const MAX_PAGE_SIZE: u32 = 1000;
+
+// Option would silently ignore invalid values, so the fields are only None if they're missing
+#[derive(FromForm, JsonSchema)]
+pub struct ListQuery<'r> {
+	#[schemars(with = "ModSort")]
+	sort: form::Result<'r, ModSort>,
+	// descending by default, except when sorting by name
+	#[schemars(with = "SortOrder")]
+	order: form::Result<'r, SortOrder>,
+	// starts at 1
+	#[schemars(with = "u32")]
+	page: form::Result<'r, u32>,
+	// at most 1000
+	#[schemars(with = "u32")]
+	per_page: form::Result<'r, u32>,
+	#[schemars(with = "u64")]
+	author: form::Result<'r, u64>,
+	#[schemars(with = "String")]
+	tag: form::Result<'r, &'r str>,
+	#[schemars(with = "String")]
+	search: form::Result<'r, &'r str>,
+}
+
+pub fn optional<'r, T>(name: &str, value: form::Result<'r, T>) -> Result<Option<T>, APIError> {
+	match value {
+		Ok(value) => Ok(Some(value)),
+		Err(errors) if errors.iter().all(|e| matches!(e.kind, ErrorKind::Missing)) => Ok(None),
+		Err(errors) => {
+			let reasons: Vec<String> = errors.iter().map(|e| e.kind.to_string()).collect();
+			Err(APIError::InvalidQuery(format!("'{name}': {}", reasons.join(", "))))
+		}
+	}
+}
+
+impl ListQuery<'_> {
+	// returns None if there are no parameters
+	fn filter(self) -> Result<Option<ModFilter>, APIError> {
+		let sort = optional("sort", self.sort)?;
+		let order = optional("order", self.order)?;
+		let page = optional("page", self.page)?;
+		let per_page = optional("per_page", self.per_page)?;
+		let author_id = optional("author", self.author)?;
+		let tag = optional("tag", self.tag)?;
+		let search = optional("search", self.search)?;
+
+		if sort.is_none() && order.is_none() && page.is_none() && per_page.is_none()
+			&& author_id.is_none() && tag.is_none() && search.is_none() {
+			return Ok(None);
+		}
+
+		let paginated = page.is_some() || per_page.is_some();
+		let per_page = per_page.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE) as i64;
+		let page = page.unwrap_or(1).max(1) as i64;
+
+		Ok(Some(ModFilter {
+			author_id,
+			tag: tag.map(str::to_owned),
+			search: search.map(str::to_owned),
+			internal_name: None,
+			mod_ids: None,
+			sort: sort.map(ModSort::key),
+			descending: match order {
+				Some(order) => order == SortOrder::Desc,
+				None => sort != Some(ModSort::Name)
+			},
+			limit: paginated.then_some(per_page),
+			offset: if paginated { (page - 1) * per_page } else { 0 },
+		}))
+	}
+}
+
+pub enum ListResponse {
+	Snapshot(LastModified<CacheResponse<PrecompressedJson>>),
+	// the number of mods on all pages is sent in the X-Total-Count header
+	Query(LastModified<CacheResponse<Value>>, i64),
+}
+
+impl<'r, 'o: 'r> Responder<'r, 'o> for ListResponse {
+	fn respond_to(self, req: &'r Request<'_>) -> response::Result<'o> {
+		match self {
+			ListResponse::Snapshot(snapshot) => snapshot.respond_to(req),
+			ListResponse::Query(page, total) => {
+				let mut response = page.respond_to(req)?;
+				response.set_header(Header::new("X-Total-Count", total.to_string()));
+				Ok(response)
+			}
+		}
+	}
+}
 
-	Ok(serde_json::json!(mods))
+// the whole list is served from the snapshot, filtered, sorted or paginated lists are queried
+#[get("/list?<query..>")]
+pub async fn list_1_4(query: ListQuery<'_>, state: &State<Api14State>) -> Result<ListResponse, APIError> {
+	let Some(filter) = query.filter()? else {
+		let (body, synced_at) = state.list_snapshot.get(&state.db).await?;
+		return Ok(ListResponse::Snapshot(LastModified {
+			responder: CacheResponse::Public {
+				responder: PrecompressedJson(body),
+				max_age: 600,
+				must_revalidate: true,
+			},
+			last_modified: synced_at,
+		}));
+	};
+
+	let mods = db::query_mods(&filter, &state.db).await?;
+	let total = db::count_mods(&filter, &state.db).await?;
+	let synced_at = db::last_sync(SyncedTable::Mods, &state.db).await?;
+
+	let response: Result<LastModified<CacheResponse<Value>>, APIError> = synced_json!(mods, synced_at, 600, true);
+	Ok(ListResponse::Query(response?, total))
 }
 
 #[get("/list_authors")]
-pub async fn list_authors(state: &State<Api14State>) -> Result<Value, APIError> {
+pub async fn list_authors(state: &State<Api14State>) -> Result<LastModified<CacheResponse<Value>>, APIError> {
 	let db: &PgPool = &state.db;
 
 	let rows = sqlx::query!(
@@ -158,7 +240,7 @@ pub async fn list_authors(state: &State<Api14State>) -> Result<Value, APIError>
 				'total_downloads', SUM(downloads_total)::BIGINT,
 				'total_views', SUM(views)::BIGINT,
 				'total_favorited', SUM(favorited)::BIGINT
-			) AS result
+			) AS "result: Json<ListedAuthor>"
 		FROM mods
 		GROUP BY author_id
 		ORDER BY SUM(downloads_total) DESC
@@ -167,7 +249,7 @@ pub async fn list_authors(state: &State<Api14State>) -> Result<Value, APIError>
 	.fetch_all(db)
 	.await?;
 
-	Ok(Value::Array(
-		rows.into_iter().filter_map(|r| r.result).collect()
-	))
+	let authors: Vec<ListedAuthor> = rows.into_iter().filter_map(|r| r.result).map(|author| author.0).collect();
+	let synced_at = db::last_sync(SyncedTable::Mods, db).await?;
+	synced_json!(authors, synced_at, 600, true)
 }
@@ -1,33 +1,49 @@
 pub mod db;
 
-mod mod_api;
-mod responses;
+pub mod mod_api;
+pub mod responses;
 mod author_api;
 mod history_api;
-mod list_api;
+pub mod list_api;
+mod export_api;
+#[cfg(feature = "parquet")]
+pub mod parquet_api;
 
-use std::{sync::{Arc, Mutex}};
-use responses::{AuthorInfo};
+use std::sync::Arc;
+use std::time::Duration;
+use responses::{AuthorInfo, SteamAvatar};
 use sqlx::PgPool;
-use crate::{cache::CacheMap, steamapi};
+use crate::{cache::{Cache, CacheAdmin, CacheFactory}, name_cache::NameCache, steamapi};
 use rocket::response::content::RawHtml;
+use list_api::ListSnapshot;
 
 pub struct Api14State {
 	pub steam_api_key: Arc<String>,
 	pub db: Arc<PgPool>,
-	pub author_cache: Arc<Mutex<CacheMap<u64, AuthorInfo>>>,
-	pub mod_cache: Arc<Mutex<CacheMap<u64, steamapi::PublishedFileDetails>>>,
+	pub name_cache: Arc<NameCache>,
+	pub author_cache: Arc<Cache<u64, AuthorInfo>>,
+	pub mod_cache: Arc<Cache<u64, steamapi::PublishedFileDetails>>,
+	pub avatar_cache: Arc<Cache<u64, Option<SteamAvatar>>>,
+	pub list_snapshot: Arc<ListSnapshot>,
 }
 
 impl Api14State {
-	pub fn init(steam_api_key: Arc<String>, db: Arc<PgPool>) -> Api14State {
+	pub fn init(steam_api_key: Arc<String>, db: Arc<PgPool>, name_cache: Arc<NameCache>, caches: &CacheFactory) -> Api14State {
+		let revalidated = caches.stale_while_revalidate();
 		Api14State { 
 			steam_api_key,
 			db,
-			author_cache: Arc::new(Mutex::new(CacheMap::new())),
-			mod_cache: Arc::new(Mutex::new(CacheMap::new())),
+			name_cache,
+			author_cache: revalidated.shared("1.4_author", Duration::from_secs(3600), 1000),
+			mod_cache: revalidated.shared("1.4_mod", Duration::from_secs(3600), 5000),
+			avatar_cache: caches.shared("1.4_avatar", Duration::from_secs(1800), 10000),
+			list_snapshot: Arc::new(ListSnapshot::new()),
 		}
 	}
+
+	pub fn caches(&self) -> Vec<Arc<dyn CacheAdmin>> {
+		vec![self.author_cache.clone(), self.mod_cache.clone(), self.avatar_cache.clone()]
+	}
 }
 
 #[get("/")]
@@ -40,6 +56,7 @@ fn index_1_4() -> RawHtml<&'static str> {
 		<a href="/1.4/list">list</a><br>
 		<a href="/1.4/list_authors">list_authors</a><br>
 		<a href="/1.4/history">history</a><br>
+		<a href="/1.4/export">export</a><br>
 
 		<br>
 		<a href="/">go back</a><br>
@@ -47,21 +64,29 @@ fn index_1_4() -> RawHtml<&'static str> {
 }
 
 use mod_api::{index_mod_1_4, count_1_4, mod_1_4, mod_1_4_str};
-use author_api::{index_author_1_4, author_1_4, author_1_4_str, get_steam_avatar};
+use author_api::{index_author_1_4, author_1_4, author_1_4_str, author_profile_1_4, author_profile_1_4_str, get_steam_avatar};
 use list_api::{list_1_4, list_authors};
+use export_api::{index_export, export_mods_ndjson, export_mods_csv, export_history_ndjson, export_history_csv};
 use history_api::{index_history, index_history_mod, history_mod, history_mod_str, index_history_author, history_author, history_author_str, history_global};
 
 pub fn get_routes() -> Vec<rocket::Route> {
-	routes![
+	let routes = routes![
 		index_1_4, 
 		count_1_4, 
 		index_author_1_4, author_1_4, author_1_4_str, 
+		author_profile_1_4, author_profile_1_4_str,
 		index_mod_1_4, mod_1_4, mod_1_4_str, 
 		list_1_4, list_authors, 
 		index_history,
 		index_history_mod, history_mod, history_mod_str, 
 		index_history_author, history_author, history_author_str,
 		history_global,
+		index_export, export_mods_ndjson, export_mods_csv, export_history_ndjson, export_history_csv,
 		get_steam_avatar
-	]
+	];
+
+	#[cfg(feature = "parquet")]
+	let routes = [routes, routes![parquet_api::export_mods_parquet, parquet_api::export_history_parquet, parquet_api::export_history_months]].concat();
+
+	routes
 }
\ No newline at end of file
@@ -1,10 +1,13 @@
 extern crate reqwest;
 
+use std::sync::Arc;
+use std::time::Duration;
 use rocket::State;
 use rocket::response::content::RawHtml;
 use rocket::serde::json::serde_json::{self, Value};
 use rocket_cache_response::CacheResponse;
-use crate::{APIError, cache, cached_json, steamapi};
+use crate::{APIError, stale_json, steamapi};
+use crate::cache::Stale;
 use super::{responses::*, Api14State};
 
 #[get("/mod")]
@@ -138,32 +141,28 @@ pub fn get_filtered_mod_info(publishedfiledetail: &steamapi::PublishedFileDetail
 
 
 #[get("/mod/<modid>", rank=1)]
-pub async fn mod_1_4(modid: u64, state: &State<Api14State>) -> Result<CacheResponse<Value>, APIError> {
-	let mod_data = get_mod_data(modid, state).await?;
+pub async fn mod_1_4(modid: u64, state: &State<Api14State>) -> Result<Stale<CacheResponse<Value>>, APIError> {
+	let (mod_data, stale_for) = get_mod_data(modid, state).await?;
 
 	let filtered_data = get_filtered_mod_info(&mod_data);
-	return cached_json!(filtered_data, 3600, false);
+	return stale_json!(filtered_data, stale_for, 3600, false);
 }
 
 #[get("/mod/<modname>", rank=2)]
-pub async fn mod_1_4_str(modname: &str, state: &State<Api14State>) -> Result<CacheResponse<Value>, APIError> {
-	let mod_id = steamapi::modname_to_modid(modname, &state.steam_api_key).await?;
-	let mod_data = get_mod_data(mod_id, state).await?;
+pub async fn mod_1_4_str(modname: &str, state: &State<Api14State>) -> Result<Stale<CacheResponse<Value>>, APIError> {
+	let mod_id = state.name_cache.mod_id(modname, &state.steam_api_key).await?;
+	let (mod_data, stale_for) = get_mod_data(mod_id, state).await?;
 
 	let filtered_data = get_filtered_mod_info(&mod_data);
-	return cached_json!(filtered_data, 3600, false);
+	return stale_json!(filtered_data, stale_for, 3600, false);
 }
 
-async fn get_mod_data(modid: u64, state: &State<Api14State>) -> Result<steamapi::PublishedFileDetails, APIError> {
-	return match cache::lock_and_get(&state.mod_cache, modid, 3600) {
-		Some(cached_value) => Ok(cached_value),
-		None => {
-			let details = steamapi::get_mod_info(modid, &state.steam_api_key).await?;
-
-			// update cache value
-			Ok(cache::lock_and_update(&state.mod_cache, modid, details))
-		}
-	}
+// also returns for how long the data has been expired
+pub async fn get_mod_data(modid: u64, state: &Api14State) -> Result<(steamapi::PublishedFileDetails, Option<Duration>), APIError> {
+	let steam_api_key = Arc::clone(&state.steam_api_key);
+	state.mod_cache.get_or_revalidate_with(modid, move || async move {
+		steamapi::get_mod_info(modid, &steam_api_key).await
+	}).await
 }
 
 pub async fn get_filtered_mod_list(steam_api_key: &str) -> Result<Vec<ModInfo>, APIError> {
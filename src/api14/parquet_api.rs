@@ -0,0 +1,179 @@
+use std::path::Path;
+use std::sync::Arc;
+use arrow_array::{ArrayRef, ArrowPrimitiveType, PrimitiveArray, RecordBatch, StringArray};
+use arrow_array::types::{Date32Type, Float64Type, Int32Type, Int64Type};
+use chrono::{Months, NaiveDate};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::{Compression, ZstdLevel};
+use parquet::errors::ParquetError;
+use parquet::file::properties::WriterProperties;
+use rocket::{tokio, State};
+use rocket::futures::TryStreamExt;
+use rocket::http::ContentType;
+use rocket::serde::json::{serde_json, Value};
+use rocket_cache_response::CacheResponse;
+use sqlx::PgPool;
+use crate::{APIError, synced_json};
+use crate::conditional::LastModified;
+use super::{db::{self, ModHistoryRow, ModsRow, SyncedTable}, export_api::Export, responses::HistoryMonth, Api14State};
+
+fn primitive<A: ArrowPrimitiveType, T>(rows: &[T], value: impl Fn(&T) -> Option<A::Native>) -> ArrayRef {
+	Arc::new(rows.iter().map(value).collect::<PrimitiveArray<A>>())
+}
+
+fn strings<T>(rows: &[T], value: impl Fn(&T) -> Option<&str>) -> ArrayRef {
+	Arc::new(rows.iter().map(value).collect::<StringArray>())
+}
+
+// columns are (name, values, nullable)
+fn write_parquet(columns: Vec<(&str, ArrayRef, bool)>) -> Result<Vec<u8>, ParquetError> {
+	let batch = RecordBatch::try_from_iter_with_nullable(columns)?;
+	let properties = WriterProperties::builder()
+		.set_compression(Compression::ZSTD(ZstdLevel::default()))
+		.build();
+
+	let mut writer = ArrowWriter::try_new(Vec::new(), batch.schema(), Some(properties))?;
+	writer.write(&batch)?;
+	writer.into_inner()
+}
+
+fn mods_parquet(rows: &[ModsRow]) -> Result<Vec<u8>, ParquetError> {
+	write_parquet(vec![
+		("mod_id", primitive::<Int64Type, _>(rows, |r| Some(r.mod_id)), false),
+		("display_name", strings(rows, |r| Some(&r.display_name)), false),
+		("internal_name", strings(rows, |r| Some(&r.internal_name)), false),
+		("author", strings(rows, |r| Some(&r.author)), false),
+		("author_id", primitive::<Int64Type, _>(rows, |r| Some(r.author_id)), false),
+		("modside", strings(rows, |r| Some(&r.modside)), false),
+		("homepage", strings(rows, |r| Some(&r.homepage)), false),
+		("mod_references", strings(rows, |r| Some(&r.mod_references)), false),
+		("num_versions", primitive::<Int32Type, _>(rows, |r| Some(r.num_versions)), false),
+		("time_created", primitive::<Int64Type, _>(rows, |r| Some(r.time_created)), false),
+		("time_updated", primitive::<Int64Type, _>(rows, |r| Some(r.time_updated)), false),
+		("workshop_icon_url", strings(rows, |r| Some(&r.workshop_icon_url)), false),
+		("description", strings(rows, |r| r.description.as_deref()), true),
+		("downloads_total", primitive::<Int32Type, _>(rows, |r| Some(r.downloads_total)), false),
+		("favorited", primitive::<Int32Type, _>(rows, |r| Some(r.favorited)), false),
+		("followers", primitive::<Int32Type, _>(rows, |r| Some(r.followers)), false),
+		("views", primitive::<Int64Type, _>(rows, |r| Some(r.views)), false),
+		("playtime", strings(rows, |r| Some(&r.playtime)), false),
+		("num_comments", primitive::<Int32Type, _>(rows, |r| Some(r.num_comments)), false),
+		("score", primitive::<Float64Type, _>(rows, |r| Some(r.score)), false),
+		("votes_up", primitive::<Int32Type, _>(rows, |r| Some(r.votes_up)), false),
+		("votes_down", primitive::<Int32Type, _>(rows, |r| Some(r.votes_down)), false),
+		("youtube", strings(rows, |r| r.youtube.as_deref()), true),
+		("twitter", strings(rows, |r| r.twitter.as_deref()), true),
+		("reddit", strings(rows, |r| r.reddit.as_deref()), true),
+		("facebook", strings(rows, |r| r.facebook.as_deref()), true),
+		("sketchfab", strings(rows, |r| r.sketchfab.as_deref()), true),
+	])
+}
+
+fn history_parquet(rows: &[ModHistoryRow]) -> Result<Vec<u8>, ParquetError> {
+	write_parquet(vec![
+		("date", primitive::<Date32Type, _>(rows, |r| Some(Date32Type::from_naive_date(r.date))), false),
+		("mod_id", primitive::<Int64Type, _>(rows, |r| Some(r.mod_id)), false),
+		("author_id", primitive::<Int64Type, _>(rows, |r| r.author_id), true),
+		("downloads_total", primitive::<Int32Type, _>(rows, |r| r.downloads_total), true),
+		("views", primitive::<Int64Type, _>(rows, |r| r.views), true),
+		("followers", primitive::<Int32Type, _>(rows, |r| r.followers), true),
+		("favorited", primitive::<Int32Type, _>(rows, |r| r.favorited), true),
+		("votes_up", primitive::<Int32Type, _>(rows, |r| r.votes_up), true),
+		("votes_down", primitive::<Int32Type, _>(rows, |r| r.votes_down), true),
+		("score", primitive::<Float64Type, _>(rows, |r| r.score), true),
+		("num_comments", primitive::<Int32Type, _>(rows, |r| r.num_comments), true),
+		("playtime", primitive::<Int64Type, _>(rows, |r| r.playtime), true),
+		("time_updated", primitive::<Int64Type, _>(rows, |r| r.time_updated), true),
+		("version", strings(rows, |r| r.version.as_deref()), true),
+	])
+}
+
+async fn encode<T: Send + 'static>(rows: Vec<T>, to_parquet: fn(&[T]) -> Result<Vec<u8>, ParquetError>) -> Result<Vec<u8>, APIError> {
+	let file = tokio::task::spawn_blocking(move || to_parquet(&rows)).await
+		.map_err(|e| {
+			log::error!("Writing the parquet file failed: {e}");
+			APIError::ExportError(format!("writing the parquet file failed: {e}"))
+		})??;
+	Ok(file)
+}
+
+async fn mods_file(db: &PgPool) -> Result<Vec<u8>, APIError> {
+	let rows: Vec<ModsRow> = db::stream_mods(db).try_collect().await?;
+	encode(rows, mods_parquet).await
+}
+
+// 'month' is the first day of the month
+async fn history_file(month: NaiveDate, db: &PgPool) -> Result<Vec<u8>, APIError> {
+	let last_day = month.checked_add_months(Months::new(1)).and_then(|next| next.pred_opt());
+	let rows: Vec<ModHistoryRow> = db::stream_mod_history(Some(month), last_day, db).try_collect().await?;
+	encode(rows, history_parquet).await
+}
+
+fn parquet_file(file: Vec<u8>, filename: String, last_modified: Option<chrono::DateTime<chrono::Utc>>) -> LastModified<CacheResponse<Export<Vec<u8>>>> {
+	LastModified {
+		responder: CacheResponse::Public {
+			responder: Export::new(file, ContentType::new("application", "vnd.apache.parquet"), filename),
+			max_age: 3600,
+			must_revalidate: true,
+		},
+		last_modified,
+	}
+}
+
+#[get("/export/mods.parquet")]
+pub async fn export_mods_parquet(state: &State<Api14State>) -> Result<LastModified<CacheResponse<Export<Vec<u8>>>>, APIError> {
+	let file = mods_file(&state.db).await?;
+	let synced_at = db::last_sync(SyncedTable::Mods, &state.db).await?;
+	Ok(parquet_file(file, "mods.parquet".to_owned(), synced_at))
+}
+
+// the history is split into one file per month, listed at /export/history/months
+#[get("/export/history/<file>")]
+pub async fn export_history_parquet(file: &str, state: &State<Api14State>) -> Result<LastModified<CacheResponse<Export<Vec<u8>>>>, APIError> {
+	let month = file.strip_suffix(".parquet")
+		.and_then(|month| NaiveDate::parse_from_str(&format!("{month}-01"), "%Y-%m-%d").ok())
+		.ok_or_else(|| APIError::InvalidQuery("'month': expected a file like 2024-01.parquet".to_owned()))?;
+
+	let file = history_file(month, &state.db).await?;
+	let synced_at = db::last_sync(SyncedTable::ModHistory, &state.db).await?;
+	Ok(parquet_file(file, format!("history-{}.parquet", month.format("%Y-%m")), synced_at))
+}
+
+#[get("/export/history/months")]
+pub async fn export_history_months(state: &State<Api14State>) -> Result<LastModified<CacheResponse<Value>>, APIError> {
+	let months: Vec<HistoryMonth> = db::history_months(&state.db).await?
+		.into_iter()
+		.map(|(month, rows)| {
+			let month = month.format("%Y-%m");
+			HistoryMonth {
+				month: month.to_string(),
+				rows: rows as u64,
+				url: format!("/1.4/export/history/{month}.parquet")
+			}
+		})
+		.collect();
+
+	let synced_at = db::last_sync(SyncedTable::ModHistory, &state.db).await?;
+	synced_json!(months, synced_at, 3600, true)
+}
+
+// writes mods.parquet and mod_history/month=YYYY-MM/data.parquet into 'dir',
+// the hive style directories let duckdb and polars filter the months by path
+pub async fn write_dir(dir: &Path, db: &PgPool) -> Result<(), APIError> {
+	let write = |path: &Path, file: Vec<u8>| {
+		path.parent().map_or(Ok(()), std::fs::create_dir_all)
+			.and_then(|_| std::fs::write(path, file))
+			.map_err(|e| APIError::ExportError(format!("{}: {e}", path.display())))
+	};
+
+	let path = dir.join("mods.parquet");
+	write(&path, mods_file(db).await?)?;
+	println!("wrote {}", path.display());
+
+	for (month, rows) in db::history_months(db).await? {
+		let path = dir.join(format!("mod_history/month={}/data.parquet", month.format("%Y-%m")));
+		write(&path, history_file(month, db).await?)?;
+		println!("wrote {} ({rows} rows)", path.display());
+	}
+	Ok(())
+}
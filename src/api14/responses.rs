@@ -1,23 +1,17 @@
+use chrono::NaiveDate;
 use rocket::serde::{Serialize, Deserialize};
-use crate::steamapi;
+use schemars::JsonSchema;
+use crate::{api_error::ErrorResponse, steamapi};
 
-#[derive(Serialize, Deserialize)]
-#[serde(crate = "rocket::serde")]
-pub enum ModSide {
-	Both,
-	Client,
-	Server,
-	NoSync
-}
-
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
 #[serde(crate = "rocket::serde")]
+#[schemars(rename = "ModVersion_1_4")]
 pub struct ModVersion {
 	pub mod_version: String,
 	pub tmodloader_version: String,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
 #[serde(crate = "rocket::serde")]
 pub struct ModSocials {
 	pub youtube: Option<String>,
@@ -27,8 +21,9 @@ pub struct ModSocials {
 	pub sketchfab: Option<String>
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
 #[serde(crate = "rocket::serde")]
+#[schemars(rename = "ModInfo_1_4")]
 pub struct ModInfo {
 	pub display_name: String,
 	pub internal_name: String,
@@ -56,15 +51,153 @@ pub struct ModInfo {
 	pub socials: Option<ModSocials>
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
 #[serde(crate = "rocket::serde")]
+#[schemars(rename = "AuthorInfo_1_4")]
 pub struct AuthorInfo {
 	pub steam_id: String,
 	pub steam_name: String,
 	pub steam_avatar: String,
+	pub country_code: Option<String>,
 	pub mods: Vec<ModInfo>,
 	pub total: u32,
 	pub total_downloads: u64,
 	pub total_favorites: u64,
 	pub total_views: u64,
 }
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct AuthorName {
+	pub name: String,
+	pub first_seen: NaiveDate,
+	pub last_seen: NaiveDate,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct CreditedMod {
+	pub mod_id: u64,
+	pub display_name: String,
+	pub internal_name: String,
+	pub uploader_id: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct CreditedName {
+	pub name: String,
+	pub mods: Vec<CreditedMod>,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct AuthorProfile {
+	pub steam_id: String,
+	pub steam_name: String,
+	pub display_names: Vec<AuthorName>,
+	pub first_seen: Option<NaiveDate>,
+	pub last_seen: Option<NaiveDate>,
+	pub names: Vec<CreditedName>,
+	pub co_authors: Vec<CreditedName>,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct SteamAvatar {
+	pub persona_name: String,
+	pub avatar: String,
+	pub avatar_medium: String,
+	pub avatar_full: String,
+}
+
+impl From<&steamapi::SteamUserInfo> for SteamAvatar {
+	fn from(user: &steamapi::SteamUserInfo) -> Self {
+		SteamAvatar {
+			persona_name: user.personaname.clone(),
+			avatar: user.avatar.clone(),
+			avatar_medium: user.avatarmedium.clone(),
+			avatar_full: user.avatarfull.clone(),
+		}
+	}
+}
+
+// every requested steamid either resolves to an avatar or to the error that occured for it
+#[derive(Serialize, JsonSchema)]
+#[serde(crate = "rocket::serde", untagged)]
+pub enum AvatarEntry {
+	Found(SteamAvatar),
+	Error(ErrorResponse),
+}
+
+// a month of the history export
+#[cfg(feature = "parquet")]
+#[derive(Serialize, JsonSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct HistoryMonth {
+	// like 2024-01
+	pub month: String,
+	pub rows: u64,
+	pub url: String,
+}
+
+// the rows of /history are built as json by postgres and read into these
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct HistoryVoteData {
+	pub votes_up: Option<i32>,
+	pub votes_down: Option<i32>,
+	pub score: Option<f64>,
+}
+
+// a mod on one day, the fields are null for days before they were recorded
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct ModHistoryEntry {
+	pub date: NaiveDate,
+	pub mod_id: i64,
+	pub author_id: Option<String>,
+	pub downloads_total: Option<i32>,
+	pub views: Option<i64>,
+	pub followers: Option<i32>,
+	pub favorited: Option<i32>,
+	pub vote_data: HistoryVoteData,
+	pub num_comments: Option<i32>,
+	pub playtime: Option<i64>,
+	pub time_updated: Option<i64>,
+	pub version: Option<String>,
+}
+
+// all mods on one day
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct GlobalHistoryEntry {
+	pub date: NaiveDate,
+	pub downloads_total: Option<i64>,
+	pub views_total: Option<i64>,
+	pub followers_total: Option<i64>,
+	pub favorited_total: Option<i64>,
+	pub playtime_total: Option<i64>,
+	pub comments_total: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct ListedAuthorMod {
+	pub mod_id: i64,
+	pub display_name: String,
+	pub internal_name: String,
+}
+
+// a row of /list_authors, also built by postgres
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct ListedAuthor {
+	pub author_id: String,
+	// every 'Author' field of their mods
+	pub author_names: Vec<String>,
+	pub mods: Vec<ListedAuthorMod>,
+	pub total_downloads: i64,
+	pub total_views: i64,
+	pub total_favorited: i64,
+}
@@ -1,94 +1,230 @@
 use rocket::response::{self, Responder, Response};
 use rocket::Request;
-use rocket::http::Status;
+use rocket::http::{Header, Status};
 use rocket::serde::json::Json;
 
 use rocket::serde::Serialize;
+use schemars::JsonSchema;
+use crate::request_id::RequestId;
 
-#[derive(Serialize)]
+// how long clients should wait if steam didn't say how long it's rate limiting us
+const DEFAULT_RETRY_AFTER: u64 = 60;
+
+#[derive(Serialize, JsonSchema)]
 #[serde(crate = "rocket::serde")]
-struct ErrorResponse {
+pub struct ErrorResponse {
+	// stable, machine readable error code
+	code: String,
+	status: u16,
+	// name of the error, kept for older clients
 	error: String,
 	message: String,
+	// only missing for errors which are part of a successful response
+	#[serde(skip_serializing_if = "Option::is_none")]
+	request_id: Option<String>,
+}
+
+impl From<&APIError> for ErrorResponse {
+	fn from(e: &APIError) -> Self {
+		ErrorResponse {
+			code: e.code().to_owned(),
+			status: e.status().code,
+			error: e.name().to_owned(),
+			message: e.to_string(),
+			request_id: None,
+		}
+	}
 }
 
 pub enum APIError {
+	// a response from steam or the 1.3 server could not be parsed
 	JSONError(String),
+	// steam or the 1.3 server could not be reached or responded with an error
 	ReqwestError(String),
+	UpstreamTimeout(String),
+	// steam is rate limiting us, with the seconds to wait if it said so
+	RateLimited(Option<u64>),
 	SteamNameNotResolveable(String),
 	SteamIDNotFound(u64),
 	InvalidSteamID(u64),
+	// the name isn't a valid internal mod name
 	InvalidModName(String),
+	ModNameNotFound(String),
+	// no mod has this id
 	InvalidModID(u64),
+	InvalidQuery(String),
 	ScrapeError(String),
-	DBError(String)
+	DBError(String),
+	// our own data could not be serialized
+	SerializeError(String),
+	#[cfg(feature = "parquet")]
+	ExportError(String)
 }
 
 impl std::fmt::Display for APIError {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-		f.write_str(match self {
-			APIError::JSONError(_) => "JSONError",
-			APIError::ReqwestError(_) => "ReqwestError",
-			APIError::SteamNameNotResolveable(_) => "SteamNameNotResolveable",
-			APIError::SteamIDNotFound(_) => "SteamNameNotResolveable",
-			APIError::InvalidSteamID(_) => "InvalidSteamID",
-			APIError::InvalidModName(_) => "InvalidModName",
-			APIError::InvalidModID(_) => "InvalidModID",
-			APIError::ScrapeError(_) => "ScrapeError",
-			APIError::DBError(_) => "DBError"
-		})
+		match self {
+			APIError::JSONError(msg) => write!(f, "Could not parse json: '{msg}'"),
+			APIError::ReqwestError(msg) => write!(f, "Could not complete the upstream request: '{msg}'"),
+			APIError::UpstreamTimeout(msg) => write!(f, "The upstream request timed out: '{msg}'"),
+			APIError::RateLimited(_) => write!(f, "Steam is rate limiting requests, try again later"),
+			APIError::SteamNameNotResolveable(name) => write!(f, "No steamid found for the specified steam name of: '{name}'"),
+			APIError::SteamIDNotFound(steamid) => write!(f, "No steam user found for the specified steam id of: '{steamid}'"),
+			APIError::InvalidSteamID(steamid) => write!(f, "The steamid '{steamid}' is invalid"),
+			APIError::InvalidModName(name) => write!(f, "The mod name '{name}' is invalid"),
+			APIError::ModNameNotFound(name) => write!(f, "Could not find a mod with the provided name: '{name}'"),
+			APIError::InvalidModID(id) => write!(f, "Could not find a mod with the id '{id}'"),
+			APIError::InvalidQuery(msg) => write!(f, "Invalid query parameter {msg}"),
+			APIError::ScrapeError(msg) => write!(f, "Could not scrape html: '{msg}'"),
+			APIError::DBError(msg) => write!(f, "An Error occured accessing the Database: '{msg}'"),
+			APIError::SerializeError(msg) => write!(f, "Could not serialize the response: '{msg}'"),
+			#[cfg(feature = "parquet")]
+			APIError::ExportError(msg) => write!(f, "Could not write the export: '{msg}'"),
+		}
 	}
 }
 
 impl From<reqwest::Error> for APIError {
-    fn from(e: reqwest::Error) -> Self {
-		log::warn!("{}", e.to_string());
-		APIError::ReqwestError(e.without_url().to_string())
-    }
-}
-
-impl From<rocket::serde::json::serde_json::Error> for APIError {
-    fn from(e: rocket::serde::json::serde_json::Error) -> Self {
-		log::warn!("{}", e.to_string());
-		APIError::JSONError(e.to_string())
-    }
+	fn from(e: reqwest::Error) -> Self {
+		log::warn!("{e}");
+		let timeout = e.is_timeout();
+		let decode = e.is_decode();
+		let rate_limited = e.status() == Some(reqwest::StatusCode::TOO_MANY_REQUESTS);
+		let msg = e.without_url().to_string();
+		if timeout {
+			APIError::UpstreamTimeout(msg)
+		} else if rate_limited {
+			APIError::RateLimited(None)
+		} else if decode {
+			APIError::JSONError(msg)
+		} else {
+			APIError::ReqwestError(msg)
+		}
+	}
 }
 
 impl From<scraper::error::SelectorErrorKind<'_>> for APIError {
 	fn from(e: scraper::error::SelectorErrorKind) -> Self {
-		log::warn!("{}", e.to_string());
+		log::warn!("{e}");
 		APIError::ScrapeError(e.to_string())
 	}
 }
 
 impl From<sqlx::Error> for APIError {
 	fn from(e: sqlx::Error) -> Self {
-		log::warn!("{}", e.to_string());
+		log::warn!("{e}");
 		APIError::DBError(e.to_string())
 	}
 }
 
+#[cfg(feature = "parquet")]
+impl From<parquet::errors::ParquetError> for APIError {
+	fn from(e: parquet::errors::ParquetError) -> Self {
+		log::warn!("{e}");
+		APIError::ExportError(e.to_string())
+	}
+}
+
+// only for serializing our own data, upstream responses which can't be parsed are a JSONError
+pub fn serialize_error(e: rocket::serde::json::serde_json::Error) -> APIError {
+	log::error!("{e}");
+	APIError::SerializeError(e.to_string())
+}
+
+// turns error statuses of upstream responses into errors, keeping the Retry-After of 429 responses
+pub fn check_upstream(res: reqwest::Response) -> Result<reqwest::Response, APIError> {
+	if res.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+		let retry_after = res.headers().get(reqwest::header::RETRY_AFTER)
+			.and_then(|value| value.to_str().ok()?.parse().ok());
+		log::warn!("Rate limited by {}, retry after {retry_after:?}", res.url().host_str().unwrap_or_default());
+		return Err(APIError::RateLimited(retry_after));
+	}
+	Ok(res.error_for_status()?)
+}
+
+impl APIError {
+	fn name(&self) -> &'static str {
+		match self {
+			APIError::JSONError(_) => "JSONError",
+			APIError::ReqwestError(_) => "ReqwestError",
+			APIError::UpstreamTimeout(_) => "UpstreamTimeout",
+			APIError::RateLimited(_) => "RateLimited",
+			APIError::SteamNameNotResolveable(_) => "SteamNameNotResolveable",
+			APIError::SteamIDNotFound(_) => "SteamIDNotFound",
+			APIError::InvalidSteamID(_) => "InvalidSteamID",
+			APIError::InvalidModName(_) => "InvalidModName",
+			APIError::ModNameNotFound(_) => "ModNameNotFound",
+			APIError::InvalidModID(_) => "InvalidModID",
+			APIError::InvalidQuery(_) => "InvalidQuery",
+			APIError::ScrapeError(_) => "ScrapeError",
+			APIError::DBError(_) => "DBError",
+			APIError::SerializeError(_) => "SerializeError",
+			#[cfg(feature = "parquet")]
+			APIError::ExportError(_) => "ExportError"
+		}
+	}
+
+	// these never change, clients can match on them
+	pub fn code(&self) -> &'static str {
+		match self {
+			APIError::JSONError(_) => "upstream_invalid_json",
+			APIError::ReqwestError(_) => "upstream_unavailable",
+			APIError::UpstreamTimeout(_) => "upstream_timeout",
+			APIError::RateLimited(_) => "upstream_rate_limited",
+			APIError::SteamNameNotResolveable(_) => "steam_name_not_found",
+			APIError::SteamIDNotFound(_) => "steam_user_not_found",
+			APIError::InvalidSteamID(_) => "invalid_steam_id",
+			APIError::InvalidModName(_) => "invalid_mod_name",
+			APIError::ModNameNotFound(_) | APIError::InvalidModID(_) => "mod_not_found",
+			APIError::InvalidQuery(_) => "invalid_query",
+			APIError::ScrapeError(_) => "upstream_scrape_failed",
+			APIError::DBError(_) => "database_error",
+			APIError::SerializeError(_) => "internal_error",
+			#[cfg(feature = "parquet")]
+			APIError::ExportError(_) => "export_failed"
+		}
+	}
+
+	pub fn status(&self) -> Status {
+		match self {
+			APIError::JSONError(_) | APIError::ReqwestError(_) | APIError::ScrapeError(_) => Status::BadGateway,
+			APIError::UpstreamTimeout(_) => Status::GatewayTimeout,
+			APIError::RateLimited(_) => Status::ServiceUnavailable,
+			APIError::SteamNameNotResolveable(_) | APIError::SteamIDNotFound(_)
+				| APIError::ModNameNotFound(_) | APIError::InvalidModID(_) => Status::NotFound,
+			APIError::InvalidSteamID(_) | APIError::InvalidModName(_) | APIError::InvalidQuery(_) => Status::BadRequest,
+			APIError::DBError(_) | APIError::SerializeError(_) => Status::InternalServerError,
+			#[cfg(feature = "parquet")]
+			APIError::ExportError(_) => Status::InternalServerError,
+		}
+	}
+}
+
 impl<'r> Responder<'r, 'static> for APIError {
 	fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
-		let (status, message) = match &self {
-			APIError::ScrapeError(msg) => (Status::InternalServerError, format!("Could not scrape html: '{}'", msg)),
-			APIError::JSONError(msg) => (Status::InternalServerError, format!("Could not parse request: '{}'", msg)),
-			APIError::ReqwestError(msg) => (Status::InternalServerError, format!("Could not parse json: '{}'", msg)),
-			APIError::SteamNameNotResolveable(name) => (Status::BadRequest, format!("No steamid found for the specified steam name of: '{}'", name)),
-			APIError::SteamIDNotFound(steamid) => (Status::BadRequest, format!("No steam user found for the specified steam id of: '{}'", steamid)),
-			APIError::InvalidSteamID(steamid) => (Status::BadRequest, format!("The steamid '{}' is invalid", steamid)),
-			APIError::InvalidModName(name) => (Status::BadRequest, format!("Could not find a mod with the provided name: '{}'", name)),
-			APIError::InvalidModID(id) => (Status::BadRequest, format!("Could not find a mod with the id '{}'", id)),
-			APIError::DBError(msg) => (Status::InternalServerError, format!("An Error occured accessing the Database: '{msg}'")),
-		};
-
 		let body = Json(ErrorResponse {
-			error: format!("{}", self),
-			message,
+			request_id: Some(RequestId::of(req).to_string()),
+			..ErrorResponse::from(&self)
 		});
 
-		Response::build_from(body.respond_to(req)?)
-			.status(status)
-			.ok()
+		let mut response = Response::build_from(body.respond_to(req)?);
+		response.status(self.status());
+		if let APIError::RateLimited(retry_after) = self {
+			response.header(Header::new("Retry-After", retry_after.unwrap_or(DEFAULT_RETRY_AFTER).to_string()));
+		}
+		response.ok()
 	}
 }
+
+// errors without an APIError, like unknown routes, get the same json body
+#[catch(default)]
+pub fn default_catcher(status: Status, req: &Request<'_>) -> (Status, Json<ErrorResponse>) {
+	let reason = status.reason().unwrap_or("Unknown Error");
+	(status, Json(ErrorResponse {
+		code: reason.to_lowercase().replace(' ', "_"),
+		status: status.code,
+		error: reason.replace(' ', ""),
+		message: reason.to_owned(),
+		request_id: Some(RequestId::of(req).to_string()),
+	}))
+}
@@ -0,0 +1,77 @@
+use std::hash::Hash;
+use std::num::NonZeroUsize;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use lru::LruCache;
+use super::{CacheBackend, CachedValue};
+
+struct CacheItem<T> {
+    item: T,
+    time_stamp: Instant
+}
+
+// in-process storage which evicts the least recently used entry when it's full
+pub struct MemoryBackend<K: Hash + Eq, V> {
+    entries: Mutex<LruCache<K, CacheItem<V>>>,
+    retention: Duration,
+}
+
+impl<K: Hash + Eq, V> MemoryBackend<K, V> {
+    pub fn new(retention: Duration, max_size: usize) -> MemoryBackend<K, V> {
+        MemoryBackend {
+            entries: Mutex::new(LruCache::new(NonZeroUsize::new(max_size).unwrap_or(NonZeroUsize::MIN))),
+            retention,
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<K, V> CacheBackend<K, V> for MemoryBackend<K, V> where K: Hash + Eq + Clone + Send + Sync, V: Clone + Send + Sync {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        Some(self.entries.lock().unwrap().cap().get())
+    }
+
+    async fn get(&self, key: &K) -> Option<CachedValue<V>> {
+        self.entries.lock().unwrap().get(key).map(|cached| CachedValue {
+            value: cached.item.clone(),
+            age: cached.time_stamp.elapsed(),
+        })
+    }
+
+    async fn insert(&self, key: K, value: V) -> bool {
+        let item = CacheItem { item: value, time_stamp: Instant::now() };
+        let evicted = self.entries.lock().unwrap().push(key.clone(), item);
+        // push also returns the old value when it replaces the entry of the same key
+        evicted.is_some_and(|(evicted_key, _)| evicted_key != key)
+    }
+
+    async fn remove(&self, key: &K) -> bool {
+        self.entries.lock().unwrap().pop(key).is_some()
+    }
+
+    async fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    async fn remove_expired(&self) -> u64 {
+        let mut entries = self.entries.lock().unwrap();
+        let expired: Vec<K> = entries.iter()
+            .filter(|(_, cached)| cached.time_stamp.elapsed() >= self.retention)
+            .map(|(key, _)| key.clone())
+            .collect();
+
+        for key in &expired {
+            entries.pop(key);
+        }
+
+        expired.len() as u64
+    }
+
+    async fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+}
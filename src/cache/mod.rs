@@ -0,0 +1,406 @@
+mod memory;
+#[cfg(feature = "redis")]
+mod redis;
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::future::Future;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+use rocket::serde::{DeserializeOwned, Serialize};
+use rocket::http::Header;
+use rocket::response::{self, Responder};
+use rocket::{tokio, Request};
+
+pub use memory::MemoryBackend;
+
+// a cached value and how long ago it was stored
+pub struct CachedValue<V> {
+    pub value: V,
+    pub age: Duration,
+}
+
+// storage of a cache, the cache itself takes care of expiry decisions, statistics and request coalescing
+#[rocket::async_trait]
+pub trait CacheBackend<K, V>: Send + Sync where K: Send + Sync, V: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn capacity(&self) -> Option<usize>;
+    async fn get(&self, key: &K) -> Option<CachedValue<V>>;
+    // returns true if a different entry had to be evicted to make room
+    async fn insert(&self, key: K, value: V) -> bool;
+    async fn remove(&self, key: &K) -> bool;
+    async fn clear(&self);
+    // removes entries older than the retention time and returns how many were removed
+    async fn remove_expired(&self) -> u64;
+    async fn len(&self) -> usize;
+}
+
+#[derive(Default)]
+struct CacheCounters {
+    hits: AtomicU64,
+    stale_hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+    expirations: AtomicU64,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct CacheStats {
+    pub name: &'static str,
+    pub backend: &'static str,
+    pub size: usize,
+    pub capacity: Option<usize>,
+    pub ttl: u64,
+    pub max_stale: u64,
+    pub hits: u64,
+    pub stale_hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    pub expirations: u64,
+}
+
+// a cache with a pluggable storage backend.
+// concurrent misses for the same key are coalesced, so only one of them does the actual fetch
+pub struct Cache<K: Hash + Eq, V> {
+    name: &'static str,
+    backend: Box<dyn CacheBackend<K, V>>,
+    in_flight: Mutex<HashMap<K, Arc<tokio::sync::Mutex<()>>>>,
+    ttl: Duration,
+    max_stale: Duration,
+    counters: CacheCounters,
+}
+
+impl<K, V> Cache<K, V> where K: Hash + Eq + Clone + Send + Sync, V: Clone + Send + Sync {
+    // the backend has to keep entries for ttl + max_stale
+    pub fn new(name: &'static str, ttl: Duration, max_stale: Duration, backend: Box<dyn CacheBackend<K, V>>) -> Cache<K, V> {
+        Cache {
+            name,
+            backend,
+            in_flight: Mutex::new(HashMap::new()),
+            ttl,
+            max_stale,
+            counters: CacheCounters::default(),
+        }
+    }
+
+    // returns the value if it exists and isn't expired yet
+    pub async fn get(&self, key: &K) -> Option<V> {
+        let value = self.peek(key).await;
+        let counter = if value.is_some() { &self.counters.hits } else { &self.counters.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+        value
+    }
+
+    // same as get, but doesn't count as a hit or miss
+    async fn peek(&self, key: &K) -> Option<V> {
+        match self.backend.get(key).await {
+            Some(cached) if cached.age < self.ttl => Some(cached.value),
+            _ => None
+        }
+    }
+
+    pub async fn insert(&self, key: K, value: V) {
+        if self.backend.insert(key, value).await {
+            self.counters.evictions.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub async fn remove(&self, key: &K) -> bool {
+        self.backend.remove(key).await
+    }
+
+    pub async fn clear(&self) {
+        self.backend.clear().await
+    }
+
+    pub async fn remove_expired(&self) {
+        let expired = self.backend.remove_expired().await;
+        self.counters.expirations.fetch_add(expired, Ordering::Relaxed);
+    }
+
+    pub async fn stats(&self) -> CacheStats {
+        CacheStats {
+            name: self.name,
+            backend: self.backend.name(),
+            size: self.backend.len().await,
+            capacity: self.backend.capacity(),
+            ttl: self.ttl.as_secs(),
+            max_stale: self.max_stale.as_secs(),
+            hits: self.counters.hits.load(Ordering::Relaxed),
+            stale_hits: self.counters.stale_hits.load(Ordering::Relaxed),
+            misses: self.counters.misses.load(Ordering::Relaxed),
+            evictions: self.counters.evictions.load(Ordering::Relaxed),
+            expirations: self.counters.expirations.load(Ordering::Relaxed),
+        }
+    }
+
+    // returns the cached value or fetches it. Only one fetch per key runs at a time,
+    // everyone else waiting for the same key gets the value it produced
+    pub async fn get_or_try_insert_with<F, Fut, E>(&self, key: K, fetch: F) -> Result<V, E>
+        where F: FnOnce() -> Fut, Fut: Future<Output = Result<V, E>>
+    {
+        if let Some(value) = self.get(&key).await {
+            return Ok(value);
+        }
+
+        self.fetch_and_insert(key, fetch).await
+    }
+
+    async fn fetch_and_insert<F, Fut, E>(&self, key: K, fetch: F) -> Result<V, E>
+        where F: FnOnce() -> Fut, Fut: Future<Output = Result<V, E>>
+    {
+        let lock = self.lock_key(&key);
+        let guard = lock.lock().await;
+
+        // the value might have been fetched while waiting for the lock
+        let result = match self.peek(&key).await {
+            Some(value) => Ok(value),
+            None => {
+                let result = fetch().await;
+                if let Ok(value) = &result {
+                    self.insert(key.clone(), value.clone()).await;
+                }
+                result
+            }
+        };
+
+        drop(guard);
+        self.release_key(&key, lock);
+        result
+    }
+
+    fn lock_key(&self, key: &K) -> Arc<tokio::sync::Mutex<()>> {
+        Arc::clone(self.in_flight.lock().unwrap().entry(key.clone()).or_default())
+    }
+
+    // the last one holding the lock removes it, nobody can be waiting for it anymore
+    fn release_key(&self, key: &K, lock: Arc<tokio::sync::Mutex<()>>) {
+        let mut in_flight = self.in_flight.lock().unwrap();
+        drop(lock);
+        if in_flight.get(key).is_some_and(|lock| Arc::strong_count(lock) == 1) {
+            in_flight.remove(key);
+        }
+    }
+}
+
+impl<K, V> Cache<K, V> where K: Hash + Eq + Clone + Send + Sync + 'static, V: Clone + Send + Sync + 'static {
+    // like get_or_try_insert_with, but expired values younger than ttl + max_stale are returned right away
+    // while they get refreshed in the background. Also returns for how long the value has been expired
+    pub async fn get_or_revalidate_with<F, Fut, E>(self: &Arc<Self>, key: K, fetch: F) -> Result<(V, Option<Duration>), E>
+        where F: FnOnce() -> Fut + Send + 'static, Fut: Future<Output = Result<V, E>> + Send + 'static, E: Display + Send + 'static
+    {
+        match self.backend.get(&key).await {
+            Some(cached) if cached.age < self.ttl => {
+                self.counters.hits.fetch_add(1, Ordering::Relaxed);
+                return Ok((cached.value, None));
+            }
+            Some(cached) if cached.age < self.ttl + self.max_stale => {
+                self.counters.stale_hits.fetch_add(1, Ordering::Relaxed);
+                self.revalidate(key, fetch);
+                return Ok((cached.value, Some(cached.age - self.ttl)));
+            }
+            _ => self.counters.misses.fetch_add(1, Ordering::Relaxed)
+        };
+
+        self.fetch_and_insert(key, fetch).await.map(|value| (value, None))
+    }
+
+    // refreshes the entry in the background unless someone is already fetching it.
+    // if that fails the old value stays until it's older than ttl + max_stale
+    fn revalidate<F, Fut, E>(self: &Arc<Self>, key: K, fetch: F)
+        where F: FnOnce() -> Fut + Send + 'static, Fut: Future<Output = Result<V, E>> + Send + 'static, E: Display + Send + 'static
+    {
+        let lock = self.lock_key(&key);
+        let Ok(guard) = Arc::clone(&lock).try_lock_owned() else {
+            self.release_key(&key, lock);
+            return;
+        };
+
+        let cache = Arc::clone(self);
+        tokio::spawn(async move {
+            match fetch().await {
+                Ok(value) => cache.insert(key.clone(), value).await,
+                Err(e) => log::warn!("Could not refresh an entry of the '{}' cache: {e}", cache.name)
+            }
+
+            drop(guard);
+            cache.release_key(&key, lock);
+        });
+    }
+
+    // periodically removes expired entries until the cache is dropped
+    pub fn start_expiry(cache: &Arc<Cache<K, V>>, interval: Duration) {
+        let cache: Weak<Cache<K, V>> = Arc::downgrade(cache);
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                match cache.upgrade() {
+                    Some(cache) => cache.remove_expired().await,
+                    None => break
+                }
+            }
+        });
+    }
+}
+
+const DEFAULT_MAX_STALE: Duration = Duration::from_secs(24 * 3600);
+
+// creates the caches of the api states. Caches of values that can be shared between
+// multiple instances use redis if the 'redis' feature is enabled and REDIS_URL is set
+#[derive(Clone)]
+pub struct CacheFactory {
+    #[cfg(feature = "redis")]
+    redis: Option<::redis::aio::ConnectionManager>,
+    // how long expired values may be served while they're refreshed, set with CACHE_MAX_STALE in seconds
+    max_stale: Duration,
+    serve_stale: bool,
+}
+
+impl CacheFactory {
+    pub async fn from_env() -> CacheFactory {
+        let max_stale = match std::env::var("CACHE_MAX_STALE") {
+            Ok(secs) => Duration::from_secs(secs.parse().expect("the 'CACHE_MAX_STALE' environment variable is not a number of seconds")),
+            Err(_) => DEFAULT_MAX_STALE
+        };
+
+        CacheFactory {
+            #[cfg(feature = "redis")]
+            redis: Self::connect_redis().await,
+            max_stale,
+            serve_stale: false,
+        }
+    }
+
+    #[cfg(feature = "redis")]
+    async fn connect_redis() -> Option<::redis::aio::ConnectionManager> {
+        let url = std::env::var("REDIS_URL").ok()?;
+        let client = ::redis::Client::open(url).expect("the 'REDIS_URL' environment variable is not a valid redis url");
+        let connection = ::redis::aio::ConnectionManager::new(client).await.expect("Failed to connect to redis");
+        log::info!("Using redis for shared caches");
+        Some(connection)
+    }
+
+    // caches created by the returned factory keep expired values around for get_or_revalidate_with
+    pub fn stale_while_revalidate(&self) -> CacheFactory {
+        CacheFactory { serve_stale: true, ..self.clone() }
+    }
+
+    fn max_stale(&self) -> Duration {
+        if self.serve_stale { self.max_stale } else { Duration::ZERO }
+    }
+
+    // a cache that is local to this instance
+    pub fn local<K, V>(&self, name: &'static str, ttl: Duration, max_size: usize) -> Arc<Cache<K, V>>
+        where K: Hash + Eq + Clone + Send + Sync + 'static, V: Clone + Send + Sync + 'static
+    {
+        let max_stale = self.max_stale();
+        let cache = Arc::new(Cache::new(name, ttl, max_stale, Box::new(MemoryBackend::new(ttl + max_stale, max_size))));
+        Cache::start_expiry(&cache, Duration::from_secs(600));
+        cache
+    }
+
+    // a cache that is shared between instances if redis is available
+    pub fn shared<K, V>(&self, name: &'static str, ttl: Duration, max_size: usize) -> Arc<Cache<K, V>>
+        where K: Hash + Eq + Clone + CacheKey + Send + Sync + 'static, V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static
+    {
+        #[cfg(feature = "redis")]
+        if let Some(connection) = &self.redis {
+            let max_stale = self.max_stale();
+            let backend = redis::RedisBackend::new(connection.clone(), name, ttl + max_stale);
+            return Arc::new(Cache::new(name, ttl, max_stale, Box::new(backend)));
+        }
+
+        self.local(name, ttl, max_size)
+    }
+}
+
+// sets the X-Cache-Stale header to the number of seconds a value has been expired for
+// and keeps proxies from caching it
+pub struct Stale<R> {
+    pub responder: R,
+    pub stale_for: Option<Duration>,
+}
+
+impl<'r, 'o: 'r, R: Responder<'r, 'o>> Responder<'r, 'o> for Stale<R> {
+    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'o> {
+        let mut response = self.responder.respond_to(req)?;
+        if let Some(stale_for) = self.stale_for {
+            response.set_header(Header::new("X-Cache-Stale", stale_for.as_secs().to_string()));
+            response.set_header(Header::new("Cache-Control", "no-cache"));
+        }
+        Ok(response)
+    }
+}
+
+// keys that can be converted from and to strings,
+// so that single entries can be invalidated and stored in external caches
+pub trait CacheKey: Sized {
+    fn parse_key(key: &str) -> Option<Self>;
+    #[cfg_attr(not(feature = "redis"), allow(dead_code))]
+    fn key_string(&self) -> String;
+}
+
+impl CacheKey for u64 {
+    fn parse_key(key: &str) -> Option<Self> {
+        key.parse().ok()
+    }
+
+    fn key_string(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl CacheKey for String {
+    fn parse_key(key: &str) -> Option<Self> {
+        Some(key.to_owned())
+    }
+
+    fn key_string(&self) -> String {
+        self.clone()
+    }
+}
+
+// caches with a single entry
+impl CacheKey for () {
+    fn parse_key(_: &str) -> Option<Self> {
+        Some(())
+    }
+
+    fn key_string(&self) -> String {
+        String::new()
+    }
+}
+
+// type erased access to a cache for the admin endpoints
+#[rocket::async_trait]
+pub trait CacheAdmin: Send + Sync {
+    fn name(&self) -> &'static str;
+    async fn stats(&self) -> CacheStats;
+    async fn clear(&self);
+    async fn invalidate(&self, key: &str) -> bool;
+}
+
+#[rocket::async_trait]
+impl<K, V> CacheAdmin for Cache<K, V> where K: Hash + Eq + Clone + CacheKey + Send + Sync, V: Clone + Send + Sync {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    async fn stats(&self) -> CacheStats {
+        Cache::stats(self).await
+    }
+
+    async fn clear(&self) {
+        Cache::clear(self).await
+    }
+
+    async fn invalidate(&self, key: &str) -> bool {
+        match K::parse_key(key) {
+            Some(key) => self.remove(&key).await,
+            None => false
+        }
+    }
+}
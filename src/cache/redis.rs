@@ -0,0 +1,140 @@
+use std::marker::PhantomData;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use redis::AsyncCommands;
+use redis::aio::ConnectionManager;
+use rocket::serde::{Deserialize, DeserializeOwned, Serialize};
+use rocket::serde::json::serde_json;
+use super::{CacheBackend, CacheKey, CachedValue};
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct RedisEntry<V> {
+    value: V,
+    stored_at: u64,
+}
+
+// storage shared between instances. Values are stored as json and expire through redis itself,
+// a failing redis connection is logged and treated like a cache miss
+pub struct RedisBackend<K, V> {
+    connection: ConnectionManager,
+    prefix: String,
+    retention: Duration,
+    _types: PhantomData<fn(K) -> V>,
+}
+
+impl<K, V> RedisBackend<K, V> {
+    pub fn new(connection: ConnectionManager, name: &str, retention: Duration) -> RedisBackend<K, V> {
+        RedisBackend {
+            connection,
+            prefix: format!("tmlapis:{name}:"),
+            retention,
+            _types: PhantomData,
+        }
+    }
+
+    fn redis_key(&self, key: &K) -> String where K: CacheKey {
+        format!("{}{}", self.prefix, key.key_string())
+    }
+
+    async fn keys(&self) -> Vec<String> {
+        let mut connection = self.connection.clone();
+        let keys: redis::RedisResult<Vec<String>> = async {
+            let mut iter = connection.scan_match::<_, String>(format!("{}*", self.prefix)).await?;
+            let mut keys = Vec::new();
+            while let Some(key) = iter.next_item().await {
+                keys.push(key);
+            }
+            Ok(keys)
+        }.await;
+
+        keys.unwrap_or_else(|e| {
+            log::warn!("Could not scan redis keys: {e}");
+            Vec::new()
+        })
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+#[rocket::async_trait]
+impl<K, V> CacheBackend<K, V> for RedisBackend<K, V> where K: CacheKey + Send + Sync, V: Serialize + DeserializeOwned + Send + Sync {
+    fn name(&self) -> &'static str {
+        "redis"
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        None
+    }
+
+    async fn get(&self, key: &K) -> Option<CachedValue<V>> {
+        let json: Option<String> = match self.connection.clone().get(self.redis_key(key)).await {
+            Ok(json) => json,
+            Err(e) => {
+                log::warn!("Could not read from redis: {e}");
+                return None;
+            }
+        };
+
+        let entry: RedisEntry<V> = match serde_json::from_str(&json?) {
+            Ok(entry) => entry,
+            Err(e) => {
+                log::warn!("Could not deserialize redis entry: {e}");
+                return None;
+            }
+        };
+
+        Some(CachedValue {
+            value: entry.value,
+            age: Duration::from_secs(unix_now().saturating_sub(entry.stored_at)),
+        })
+    }
+
+    async fn insert(&self, key: K, value: V) -> bool {
+        let entry = RedisEntry { value, stored_at: unix_now() };
+        let json = match serde_json::to_string(&entry) {
+            Ok(json) => json,
+            Err(e) => {
+                log::warn!("Could not serialize redis entry: {e}");
+                return false;
+            }
+        };
+
+        let result: redis::RedisResult<()> = self.connection.clone()
+            .set_ex(self.redis_key(&key), json, self.retention.as_secs().max(1))
+            .await;
+        if let Err(e) = result {
+            log::warn!("Could not write to redis: {e}");
+        }
+
+        // redis evicts on its own according to its maxmemory-policy
+        false
+    }
+
+    async fn remove(&self, key: &K) -> bool {
+        let removed: redis::RedisResult<u64> = self.connection.clone().del(self.redis_key(key)).await;
+        removed.is_ok_and(|removed| removed > 0)
+    }
+
+    async fn clear(&self) {
+        let keys = self.keys().await;
+        if keys.is_empty() {
+            return;
+        }
+
+        let result: redis::RedisResult<u64> = self.connection.clone().del(keys).await;
+        if let Err(e) = result {
+            log::warn!("Could not clear redis cache: {e}");
+        }
+    }
+
+    async fn remove_expired(&self) -> u64 {
+        // entries expire through their redis ttl
+        0
+    }
+
+    async fn len(&self) -> usize {
+        self.keys().await.len()
+    }
+}
@@ -0,0 +1,215 @@
+use std::io::{Cursor, Write};
+use std::sync::Arc;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::{ContentType, Header, Status};
+use rocket::response::{self, Responder};
+use rocket::{tokio, Request, Response};
+use crate::conditional;
+
+// bodies smaller than this aren't worth compressing
+const MIN_SIZE: usize = 1024;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Encoding {
+	Brotli,
+	Zstd,
+	Gzip
+}
+
+// in order of preference if the client accepts multiple encodings equally
+const ENCODINGS: [Encoding; 3] = [Encoding::Brotli, Encoding::Zstd, Encoding::Gzip];
+
+impl Encoding {
+	pub fn name(self) -> &'static str {
+		match self {
+			Encoding::Brotli => "br",
+			Encoding::Zstd => "zstd",
+			Encoding::Gzip => "gzip"
+		}
+	}
+
+	// 'best' is slow and meant for bodies which are compressed once and sent many times
+	pub fn compress(self, data: &[u8], best: bool) -> std::io::Result<Vec<u8>> {
+		match self {
+			Encoding::Brotli => {
+				let quality = if best { 11 } else { 5 };
+				let mut writer = brotli::CompressorWriter::new(Vec::new(), 4096, quality, 22);
+				writer.write_all(data)?;
+				Ok(writer.into_inner())
+			}
+			Encoding::Zstd => zstd::encode_all(data, if best { 19 } else { 3 }),
+			Encoding::Gzip => {
+				let level = if best { flate2::Compression::best() } else { flate2::Compression::default() };
+				let mut writer = flate2::write::GzEncoder::new(Vec::new(), level);
+				writer.write_all(data)?;
+				writer.finish()
+			}
+		}
+	}
+
+	// picks the encoding with the highest q-value in the Accept-Encoding header
+	pub fn negotiate(accept_encoding: Option<&str>) -> Option<Encoding> {
+		let accepted: Vec<(&str, f32)> = accept_encoding?.split(',')
+			.filter_map(|entry| {
+				let mut parts = entry.split(';').map(str::trim);
+				let name = parts.next().filter(|name| !name.is_empty())?;
+				let q = parts
+					.find_map(|param| param.strip_prefix("q="))
+					.map_or(Some(1.0), |q| q.parse().ok())?;
+				Some((name, q))
+			})
+			.collect();
+
+		let q_value = |name: &str| accepted.iter()
+			.find(|(n, _)| n.eq_ignore_ascii_case(name))
+			.or_else(|| accepted.iter().find(|(n, _)| *n == "*"))
+			.map_or(0.0, |&(_, q)| q);
+
+		let mut best: Option<(Encoding, f32)> = None;
+		for encoding in ENCODINGS {
+			let q = q_value(encoding.name());
+			if q > 0.0 && best.is_none_or(|(_, best_q)| q > best_q) {
+				best = Some((encoding, q));
+			}
+		}
+		best.map(|(encoding, _)| encoding)
+	}
+}
+
+fn is_compressible(content_type: &ContentType) -> bool {
+	content_type.top() == "text" || ["json", "javascript", "xml", "svg+xml"].contains(&content_type.sub().as_str())
+}
+
+// the etag of a compressed body is weak, it's the same data in a different representation
+fn weak_etag(etag: &str) -> String {
+	if etag.starts_with("W/") { etag.to_owned() } else { format!("W/{etag}") }
+}
+
+// compresses text responses with the best encoding the client accepts
+pub struct Compression;
+
+#[rocket::async_trait]
+impl Fairing for Compression {
+	fn info(&self) -> Info {
+		Info {
+			name: "Response compression",
+			kind: Kind::Response
+		}
+	}
+
+	async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+		// streamed bodies have no size and would have to be read completely
+		if res.headers().contains("Content-Encoding")
+			|| res.body().preset_size().is_none()
+			|| [Status::NoContent, Status::NotModified, Status::PartialContent].contains(&res.status())
+			|| !res.content_type().is_some_and(|ct| is_compressible(&ct)) {
+			return;
+		}
+
+		if !res.headers().get("Vary").any(|vary| vary.eq_ignore_ascii_case("Accept-Encoding")) {
+			res.adjoin_header(Header::new("Vary", "Accept-Encoding"));
+		}
+		let Some(encoding) = Encoding::negotiate(req.headers().get_one("Accept-Encoding")) else {
+			return;
+		};
+
+		let body = match res.body_mut().to_bytes().await {
+			Ok(body) => body,
+			Err(e) => {
+				log::warn!("Could not read response body: {e}");
+				return;
+			}
+		};
+
+		if body.len() < MIN_SIZE {
+			res.set_sized_body(body.len(), Cursor::new(body));
+			return;
+		}
+
+		let compressed = tokio::task::spawn_blocking(move || {
+			let compressed = encoding.compress(&body, false);
+			(body, compressed)
+		}).await;
+
+		match compressed {
+			Ok((_, Ok(compressed))) => {
+				res.set_header(Header::new("Content-Encoding", encoding.name()));
+				if let Some(etag) = res.headers().get_one("ETag").map(weak_etag) {
+					res.set_header(Header::new("ETag", etag));
+				}
+				res.set_sized_body(compressed.len(), Cursor::new(compressed));
+			}
+			Ok((body, Err(e))) => {
+				log::warn!("Could not compress response body with {}: {e}", encoding.name());
+				res.set_sized_body(body.len(), Cursor::new(body));
+			}
+			Err(e) => log::error!("Compressing the response body panicked: {e}")
+		}
+	}
+}
+
+// a json body which is compressed ahead of time with every encoding
+pub struct Precompressed {
+	json: Vec<u8>,
+	etag: String,
+	variants: Vec<(Encoding, Vec<u8>)>,
+}
+
+impl Precompressed {
+	// slow for large bodies, run it with spawn_blocking
+	pub fn new(json: Vec<u8>) -> Precompressed {
+		let variants = ENCODINGS.into_iter()
+			.filter_map(|encoding| match encoding.compress(&json, true) {
+				Ok(compressed) => Some((encoding, compressed)),
+				Err(e) => {
+					log::warn!("Could not precompress body with {}: {e}", encoding.name());
+					None
+				}
+			})
+			.collect();
+
+		Precompressed {
+			etag: conditional::etag(&json),
+			json,
+			variants,
+		}
+	}
+
+	fn variant(&self, encoding: Option<Encoding>) -> Option<usize> {
+		self.variants.iter().position(|(e, _)| Some(*e) == encoding)
+	}
+}
+
+// one variant of a precompressed body, so it can be sent without copying it
+struct Variant(Arc<Precompressed>, Option<usize>);
+
+impl AsRef<[u8]> for Variant {
+	fn as_ref(&self) -> &[u8] {
+		match self.1 {
+			Some(i) => &self.0.variants[i].1,
+			None => &self.0.json
+		}
+	}
+}
+
+// responds with the precompressed variant the client accepts
+pub struct PrecompressedJson(pub Arc<Precompressed>);
+
+impl<'r, 'o: 'r> Responder<'r, 'o> for PrecompressedJson {
+	fn respond_to(self, req: &'r Request<'_>) -> response::Result<'o> {
+		let variant = self.0.variant(Encoding::negotiate(req.headers().get_one("Accept-Encoding")));
+		let mut response = Response::build();
+		response.header(ContentType::JSON).raw_header("Vary", "Accept-Encoding");
+
+		match variant {
+			Some(i) => response
+				.raw_header("Content-Encoding", self.0.variants[i].0.name())
+				.raw_header("ETag", weak_etag(&self.0.etag)),
+			None => response.raw_header("ETag", self.0.etag.clone())
+		};
+
+		let body = Variant(self.0, variant);
+		let len = body.as_ref().len();
+		response.sized_body(len, Cursor::new(body)).ok()
+	}
+}
@@ -0,0 +1,101 @@
+use chrono::{DateTime, Utc};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::{ContentType, Header, Method, Status};
+use rocket::response::{self, Responder};
+use rocket::{Request, Response};
+use sha2::{Digest, Sha256};
+use std::io::Cursor;
+
+// format of the Last-Modified and If-Modified-Since headers
+const HTTP_DATE: &str = "%a, %d %b %Y %H:%M:%S GMT";
+
+// sets the Last-Modified header, used by routes that only change when the db is synced
+pub struct LastModified<R> {
+	pub responder: R,
+	pub last_modified: Option<DateTime<Utc>>,
+}
+
+impl<'r, 'o: 'r, R: Responder<'r, 'o>> Responder<'r, 'o> for LastModified<R> {
+	fn respond_to(self, req: &'r Request<'_>) -> response::Result<'o> {
+		let mut response = self.responder.respond_to(req)?;
+		if let Some(last_modified) = self.last_modified {
+			response.set_header(Header::new("Last-Modified", last_modified.format(HTTP_DATE).to_string()));
+		}
+		Ok(response)
+	}
+}
+
+// adds an ETag to every successful json response and answers conditional requests with 304 Not Modified
+pub struct ConditionalRequests;
+
+#[rocket::async_trait]
+impl Fairing for ConditionalRequests {
+	fn info(&self) -> Info {
+		Info {
+			name: "ETag and conditional requests",
+			kind: Kind::Response
+		}
+	}
+
+	async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+		if !matches!(req.method(), Method::Get | Method::Head)
+			|| res.status() != Status::Ok
+			|| res.content_type() != Some(ContentType::JSON) {
+			return;
+		}
+
+		// responses with precomputed bodies bring their own etag
+		let etag = match res.headers().get_one("ETag") {
+			Some(etag) => etag.to_owned(),
+			None => {
+				let body = match res.body_mut().to_bytes().await {
+					Ok(body) => body,
+					Err(e) => {
+						log::warn!("Could not read response body: {e}");
+						return;
+					}
+				};
+
+				let etag = etag(&body);
+				res.set_header(Header::new("ETag", etag.clone()));
+				res.set_sized_body(body.len(), Cursor::new(body));
+				etag
+			}
+		};
+
+		// If-Modified-Since is ignored if If-None-Match is present
+		let not_modified = match req.headers().get_one("If-None-Match") {
+			Some(if_none_match) => etag_matches(if_none_match, &etag),
+			None => match (req.headers().get_one("If-Modified-Since"), res.headers().get_one("Last-Modified")) {
+				(Some(since), Some(last_modified)) => not_modified_since(since, last_modified),
+				_ => false
+			}
+		};
+
+		if not_modified {
+			res.set_status(Status::NotModified);
+			res.set_sized_body(0, Cursor::new(Vec::new()));
+		}
+	}
+}
+
+pub fn etag(body: &[u8]) -> String {
+	// half of the hash is plenty to tell responses apart
+	let digest: String = Sha256::digest(body)[..16].iter().map(|b| format!("{b:02x}")).collect();
+	format!("\"{digest}\"")
+}
+
+// If-None-Match uses the weak comparison
+fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+	let etag = etag.trim_start_matches("W/");
+	if_none_match.split(',')
+		.map(str::trim)
+		.any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+fn not_modified_since(since: &str, last_modified: &str) -> bool {
+	match (DateTime::parse_from_rfc2822(since), DateTime::parse_from_rfc2822(last_modified)) {
+		(Ok(since), Ok(last_modified)) => last_modified.timestamp() <= since.timestamp(),
+		_ => false
+	}
+}
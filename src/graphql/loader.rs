@@ -0,0 +1,126 @@
+// batches the lookups of all resolvers on the same level of a query into one query per table
+use std::collections::HashMap;
+use std::sync::Arc;
+use async_graphql::{dataloader::Loader, Error, ErrorExtensions};
+use chrono::NaiveDate;
+use sqlx::PgPool;
+use crate::api14::{db::{self, AuthorsRow, ModFilter}, responses::ModInfo};
+use super::types::HistoryPoint;
+
+pub struct DbLoader {
+	pub db: Arc<PgPool>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ModKey(pub u64);
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AuthorKey(pub u64);
+
+// the mods an author uploaded
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AuthorModsKey(pub u64);
+
+// the history of a mod between two dates, inclusive
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HistoryKey(pub u64, pub Option<NaiveDate>, pub Option<NaiveDate>);
+
+impl DbLoader {
+	// most downloaded first
+	async fn mods(&self, mod_ids: Vec<u64>) -> Result<Vec<ModInfo>, Error> {
+		let filter = ModFilter {
+			mod_ids: Some(mod_ids),
+			sort: Some("downloads"),
+			descending: true,
+			..ModFilter::default()
+		};
+		db::query_mods(&filter, &self.db).await.map_err(|e| e.extend())
+	}
+}
+
+impl Loader<ModKey> for DbLoader {
+	type Value = ModInfo;
+	type Error = Error;
+
+	async fn load(&self, keys: &[ModKey]) -> Result<HashMap<ModKey, ModInfo>, Error> {
+		let mods = self.mods(keys.iter().map(|key| key.0).collect()).await?;
+		Ok(mods.into_iter().map(|info| (ModKey(info.mod_id), info)).collect())
+	}
+}
+
+impl Loader<AuthorKey> for DbLoader {
+	type Value = AuthorsRow;
+	type Error = Error;
+
+	async fn load(&self, keys: &[AuthorKey]) -> Result<HashMap<AuthorKey, AuthorsRow>, Error> {
+		let author_ids: Vec<u64> = keys.iter().map(|key| key.0).collect();
+		let authors = db::get_authors(&author_ids, &self.db).await.map_err(|e| e.extend())?;
+		Ok(authors.into_iter().map(|author| (AuthorKey(author.author_id as u64), author)).collect())
+	}
+}
+
+impl Loader<AuthorModsKey> for DbLoader {
+	type Value = Vec<ModInfo>;
+	type Error = Error;
+
+	async fn load(&self, keys: &[AuthorModsKey]) -> Result<HashMap<AuthorModsKey, Vec<ModInfo>>, Error> {
+		let author_ids: Vec<u64> = keys.iter().map(|key| key.0).collect();
+		let mod_ids = db::get_author_mod_ids(&author_ids, &self.db).await.map_err(|e| e.extend())?;
+		let mods = self.mods(mod_ids.into_iter().map(|(_, mod_id)| mod_id).collect()).await?;
+
+		let mut by_author: HashMap<AuthorModsKey, Vec<ModInfo>> = HashMap::new();
+		for info in mods {
+			let author_id = info.author_id.parse().unwrap_or_default();
+			by_author.entry(AuthorModsKey(author_id)).or_default().push(info);
+		}
+		Ok(by_author)
+	}
+}
+
+type DateRange = (Option<NaiveDate>, Option<NaiveDate>);
+
+// the mods of the keys by their date range, so each range is one query. The mods of a list are usually
+// all asked for the same one
+fn by_range(keys: &[HistoryKey]) -> HashMap<DateRange, Vec<u64>> {
+	let mut by_range: HashMap<DateRange, Vec<u64>> = HashMap::new();
+	for &HistoryKey(mod_id, from, to) in keys {
+		by_range.entry((from, to)).or_default().push(mod_id);
+	}
+	by_range
+}
+
+impl Loader<HistoryKey> for DbLoader {
+	type Value = Vec<HistoryPoint>;
+	type Error = Error;
+
+	async fn load(&self, keys: &[HistoryKey]) -> Result<HashMap<HistoryKey, Vec<HistoryPoint>>, Error> {
+		let mut by_mod: HashMap<HistoryKey, Vec<HistoryPoint>> = HashMap::new();
+		for ((from, to), mod_ids) in by_range(keys) {
+			let rows = db::get_mod_history(&mod_ids, from, to, &self.db).await.map_err(|e| e.extend())?;
+			for row in rows {
+				by_mod.entry(HistoryKey(row.mod_id as u64, from, to)).or_default().push(HistoryPoint::from(row));
+			}
+		}
+		Ok(by_mod)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn history_keys_are_grouped_by_range() {
+		let january = (NaiveDate::from_ymd_opt(2024, 1, 1), NaiveDate::from_ymd_opt(2024, 1, 31));
+		let keys = [
+			HistoryKey(1, january.0, january.1),
+			HistoryKey(2, None, None),
+			HistoryKey(3, january.0, january.1),
+			HistoryKey(1, None, None),
+		];
+		let by_range = by_range(&keys);
+		assert_eq!(by_range.len(), 2);
+		assert_eq!(by_range[&january], [1, 3]);
+		assert_eq!(by_range[&(None, None)], [2, 1]);
+	}
+}
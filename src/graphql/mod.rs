@@ -0,0 +1,53 @@
+// /graphql over the mods, authors and mod_history tables, so pages which need a mod, its author and
+// their other mods don't take a request for each
+mod loader;
+mod types;
+
+use std::sync::Arc;
+use async_graphql::{dataloader::{DataLoader, HashMapCache}, EmptyMutation, EmptySubscription, Error, ErrorExtensions, Schema};
+use async_graphql::http::GraphiQLSource;
+use rocket::{tokio, State};
+use rocket::response::content::RawHtml;
+use rocket::serde::json::Json;
+use crate::APIError;
+use crate::api14::Api14State;
+use loader::DbLoader;
+use types::Query;
+
+pub type GraphQLSchema = Schema<Query, EmptyMutation, EmptySubscription>;
+
+// one per request, so nothing is cached between requests
+type DbDataLoader = DataLoader<DbLoader, HashMapCache>;
+
+// graphql errors carry the same code as the json errors of the other routes
+impl ErrorExtensions for APIError {
+	fn extend(&self) -> Error {
+		Error::new(self.to_string()).extend_with(|_, e| e.set("code", self.code()))
+	}
+}
+
+pub fn schema() -> GraphQLSchema {
+	Schema::build(Query, EmptyMutation, EmptySubscription)
+		.limit_depth(10)
+		// lists count once per item, see the complexity of the fields in types.rs
+		.limit_complexity(20_000)
+		.finish()
+}
+
+#[get("/graphql")]
+fn graphiql() -> RawHtml<String> {
+	RawHtml(GraphiQLSource::build().endpoint("/graphql").finish())
+}
+
+#[post("/graphql", data = "<request>")]
+async fn graphql(request: Json<async_graphql::Request>, schema: &State<GraphQLSchema>, state: &State<Api14State>) -> Json<async_graphql::Response> {
+	let loader = DataLoader::with_cache(DbLoader { db: Arc::clone(&state.db) }, tokio::spawn, HashMapCache::default());
+	let request = request.into_inner()
+		.data(loader)
+		.data(Arc::clone(&state.db));
+	Json(schema.execute(request).await)
+}
+
+pub fn get_routes() -> Vec<rocket::Route> {
+	routes![graphiql, graphql]
+}
@@ -0,0 +1,353 @@
+use std::sync::Arc;
+use async_graphql::{Context, Object, Result, ResultExt, SimpleObject, ID};
+use chrono::{DateTime, NaiveDate, Utc};
+use sqlx::PgPool;
+use crate::api14::{db::{self, AuthorsRow, ModFilter, ModHistoryRow}, list_api::{ModSort, SortOrder, DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE}, responses::ModInfo};
+use crate::APIError;
+use super::DbDataLoader;
+use super::loader::{AuthorKey, AuthorModsKey, HistoryKey, ModKey};
+
+#[derive(SimpleObject, Clone)]
+pub struct Version {
+	pub version: String,
+	pub tmodloader_version: String,
+}
+
+#[derive(SimpleObject, Clone)]
+pub struct Tag {
+	pub tag: String,
+	pub display_name: String,
+}
+
+// the stats of a mod on one day
+#[derive(SimpleObject, Clone)]
+pub struct HistoryPoint {
+	pub date: NaiveDate,
+	pub version: Option<String>,
+	pub downloads_total: Option<i32>,
+	pub views: Option<i64>,
+	pub followers: Option<i32>,
+	pub favorited: Option<i32>,
+	pub votes_up: Option<i32>,
+	pub votes_down: Option<i32>,
+	pub score: Option<f64>,
+	pub num_comments: Option<i32>,
+	pub playtime: Option<i64>,
+	pub time_updated: Option<DateTime<Utc>>,
+}
+
+impl From<ModHistoryRow> for HistoryPoint {
+	fn from(row: ModHistoryRow) -> Self {
+		HistoryPoint {
+			date: row.date,
+			version: row.version,
+			downloads_total: row.downloads_total,
+			views: row.views,
+			followers: row.followers,
+			favorited: row.favorited,
+			votes_up: row.votes_up,
+			votes_down: row.votes_down,
+			score: row.score,
+			num_comments: row.num_comments,
+			playtime: row.playtime,
+			time_updated: row.time_updated.and_then(from_unix),
+		}
+	}
+}
+
+// steam uses 0 for missing timestamps
+fn from_unix(secs: i64) -> Option<DateTime<Utc>> {
+	(secs > 0).then(|| DateTime::from_timestamp(secs, 0)).flatten()
+}
+
+fn parse_id(name: &str, id: &ID) -> Result<u64, APIError> {
+	id.parse().map_err(|_| APIError::InvalidQuery(format!("'{name}': expected a number, got '{}'", id.as_str())))
+}
+
+pub struct Mod(pub ModInfo);
+
+#[Object]
+impl Mod {
+	async fn id(&self) -> ID {
+		ID(self.0.mod_id.to_string())
+	}
+
+	async fn internal_name(&self) -> &str {
+		&self.0.internal_name
+	}
+
+	async fn display_name(&self) -> &str {
+		&self.0.display_name
+	}
+
+	// the 'Author' field of the mod, which can name more than the uploader
+	async fn author_name(&self) -> &str {
+		&self.0.author
+	}
+
+	// the steam user who uploaded the mod
+	async fn author(&self) -> Author {
+		Author { id: self.0.author_id.parse().unwrap_or_default() }
+	}
+
+	async fn modside(&self) -> &str {
+		&self.0.modside
+	}
+
+	async fn homepage(&self) -> &str {
+		&self.0.homepage
+	}
+
+	async fn description(&self) -> Option<&str> {
+		self.0.description.as_deref()
+	}
+
+	async fn icon_url(&self) -> &str {
+		&self.0.workshop_icon_url
+	}
+
+	async fn mod_references(&self) -> &str {
+		&self.0.mod_references
+	}
+
+	async fn downloads_total(&self) -> u32 {
+		self.0.downloads_total
+	}
+
+	async fn favorited(&self) -> u32 {
+		self.0.favorited
+	}
+
+	async fn followers(&self) -> u32 {
+		self.0.followers
+	}
+
+	async fn views(&self) -> u64 {
+		self.0.views
+	}
+
+	async fn num_comments(&self) -> u32 {
+		self.0.num_comments
+	}
+
+	async fn playtime(&self) -> &str {
+		&self.0.playtime
+	}
+
+	async fn score(&self) -> Option<f64> {
+		self.0.vote_data.as_ref().map(|v| v.score)
+	}
+
+	async fn votes_up(&self) -> Option<u32> {
+		self.0.vote_data.as_ref().map(|v| v.votes_up)
+	}
+
+	async fn votes_down(&self) -> Option<u32> {
+		self.0.vote_data.as_ref().map(|v| v.votes_down)
+	}
+
+	async fn time_created(&self) -> Option<DateTime<Utc>> {
+		from_unix(self.0.time_created as i64)
+	}
+
+	async fn time_updated(&self) -> Option<DateTime<Utc>> {
+		from_unix(self.0.time_updated as i64)
+	}
+
+	async fn versions(&self) -> Vec<Version> {
+		self.0.versions.iter().map(|v| Version { version: v.mod_version.clone(), tmodloader_version: v.tmodloader_version.clone() }).collect()
+	}
+
+	async fn tags(&self) -> Vec<Tag> {
+		self.0.tags.iter().flatten().map(|t| Tag { tag: t.tag.clone(), display_name: t.display_name.clone() }).collect()
+	}
+
+	#[graphql(complexity = "NESTED_LIST_SIZE * child_complexity")]
+	async fn children(&self, ctx: &Context<'_>) -> Result<Vec<Mod>> {
+		let child_ids = self.0.children.iter().flatten().map(|&id| ModKey(id));
+		let mut children = ctx.data_unchecked::<DbDataLoader>().load_many(child_ids).await?;
+		// in the order steam lists them
+		Ok(self.0.children.iter().flatten().filter_map(|id| children.remove(&ModKey(*id))).map(Mod).collect())
+	}
+
+	// newest first, from and to are inclusive
+	#[graphql(complexity = "history_days(from, to) * child_complexity")]
+	async fn history(&self, ctx: &Context<'_>, from: Option<NaiveDate>, to: Option<NaiveDate>) -> Result<Vec<HistoryPoint>> {
+		let history = ctx.data_unchecked::<DbDataLoader>().load_one(HistoryKey(self.0.mod_id, from, to)).await?;
+		Ok(history.unwrap_or_default())
+	}
+}
+
+pub struct Author {
+	pub id: u64,
+}
+
+impl Author {
+	// None if the author's steam profile hasn't been synced
+	async fn profile(&self, ctx: &Context<'_>) -> Result<Option<AuthorsRow>> {
+		ctx.data_unchecked::<DbDataLoader>().load_one(AuthorKey(self.id)).await
+	}
+
+	async fn uploaded_mods(&self, ctx: &Context<'_>) -> Result<Vec<ModInfo>> {
+		let mods = ctx.data_unchecked::<DbDataLoader>().load_one(AuthorModsKey(self.id)).await?;
+		Ok(mods.unwrap_or_default())
+	}
+}
+
+#[Object]
+impl Author {
+	async fn id(&self) -> ID {
+		ID(self.id.to_string())
+	}
+
+	// the steam name
+	async fn name(&self, ctx: &Context<'_>) -> Result<Option<String>> {
+		Ok(self.profile(ctx).await?.map(|p| p.persona_name))
+	}
+
+	async fn profile_url(&self, ctx: &Context<'_>) -> Result<Option<String>> {
+		Ok(self.profile(ctx).await?.map(|p| p.profile_url))
+	}
+
+	async fn avatar(&self, ctx: &Context<'_>) -> Result<Option<String>> {
+		Ok(self.profile(ctx).await?.map(|p| p.avatar))
+	}
+
+	async fn avatar_medium(&self, ctx: &Context<'_>) -> Result<Option<String>> {
+		Ok(self.profile(ctx).await?.map(|p| p.avatar_medium))
+	}
+
+	async fn avatar_full(&self, ctx: &Context<'_>) -> Result<Option<String>> {
+		Ok(self.profile(ctx).await?.map(|p| p.avatar_full))
+	}
+
+	async fn country_code(&self, ctx: &Context<'_>) -> Result<Option<String>> {
+		Ok(self.profile(ctx).await?.and_then(|p| p.country_code))
+	}
+
+	// most downloaded first
+	#[graphql(complexity = "NESTED_LIST_SIZE * child_complexity")]
+	async fn mods(&self, ctx: &Context<'_>) -> Result<Vec<Mod>> {
+		Ok(self.uploaded_mods(ctx).await?.into_iter().map(Mod).collect())
+	}
+
+	async fn downloads_total(&self, ctx: &Context<'_>) -> Result<u64> {
+		Ok(self.uploaded_mods(ctx).await?.iter().map(|m| m.downloads_total as u64).sum())
+	}
+}
+
+fn page_size(per_page: Option<u32>) -> u32 {
+	per_page.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE)
+}
+
+// how a list is paged, both start at 1
+fn limit_offset(page: Option<u32>, per_page: Option<u32>) -> (i64, i64) {
+	let per_page = page_size(per_page) as i64;
+	let page = page.unwrap_or(1).max(1) as i64;
+	(per_page, (page - 1) * per_page)
+}
+
+// the complexity of a list is its size times the complexity of an item. Lists which aren't paged, like the mods
+// of an author, are assumed to have this many items
+const NESTED_LIST_SIZE: usize = 10;
+
+// the history has a row per day, without a start it's counted as four years
+fn history_days(from: Option<NaiveDate>, to: Option<NaiveDate>) -> usize {
+	let Some(from) = from else {
+		return 4 * 365;
+	};
+	let to = to.unwrap_or_else(|| Utc::now().date_naive());
+	((to - from).num_days() + 1).max(1) as usize
+}
+
+pub struct Query;
+
+#[Object]
+impl Query {
+	#[graphql(name = "mod")]
+	async fn mod_by_id(&self, ctx: &Context<'_>, id: ID) -> Result<Option<Mod>> {
+		let mod_id = parse_id("id", &id).extend()?;
+		Ok(ctx.data_unchecked::<DbDataLoader>().load_one(ModKey(mod_id)).await?.map(Mod))
+	}
+
+	// if more than one mod has the name, the most downloaded one
+	async fn mod_by_name(&self, ctx: &Context<'_>, internal_name: String) -> Result<Option<Mod>> {
+		let filter = ModFilter {
+			internal_name: Some(internal_name),
+			sort: Some("downloads"),
+			descending: true,
+			limit: Some(1),
+			..ModFilter::default()
+		};
+		let mods = db::query_mods(&filter, ctx.data_unchecked::<Arc<PgPool>>()).await.extend()?;
+		Ok(mods.into_iter().next().map(Mod))
+	}
+
+	// like /1.4/list, but always paged
+	#[allow(clippy::too_many_arguments)]
+	#[graphql(complexity = "page_size(per_page) as usize * child_complexity")]
+	async fn mods(
+		&self, ctx: &Context<'_>,
+		sort: Option<ModSort>, order: Option<SortOrder>, page: Option<u32>, per_page: Option<u32>,
+		author: Option<ID>, tag: Option<String>, search: Option<String>
+	) -> Result<Vec<Mod>> {
+		let (limit, offset) = limit_offset(page, per_page);
+		let filter = ModFilter {
+			author_id: author.map(|id| parse_id("author", &id)).transpose().extend()?,
+			tag,
+			search,
+			sort: sort.map(ModSort::key),
+			descending: match order {
+				Some(order) => order == SortOrder::Desc,
+				None => sort != Some(ModSort::Name)
+			},
+			limit: Some(limit),
+			offset,
+			..ModFilter::default()
+		};
+		let mods = db::query_mods(&filter, ctx.data_unchecked::<Arc<PgPool>>()).await.extend()?;
+		Ok(mods.into_iter().map(Mod).collect())
+	}
+
+	// null if the author has no mods and their steam profile hasn't been synced
+	async fn author(&self, ctx: &Context<'_>, id: ID) -> Result<Option<Author>> {
+		let author = Author { id: parse_id("id", &id).extend()? };
+		let found = author.profile(ctx).await?.is_some() || !author.uploaded_mods(ctx).await?.is_empty();
+		Ok(found.then_some(author))
+	}
+
+	// most downloaded first
+	#[graphql(complexity = "page_size(per_page) as usize * child_complexity")]
+	async fn authors(&self, ctx: &Context<'_>, page: Option<u32>, per_page: Option<u32>) -> Result<Vec<Author>> {
+		let (limit, offset) = limit_offset(page, per_page);
+		let author_ids = db::get_top_author_ids(limit, offset, ctx.data_unchecked::<Arc<PgPool>>()).await.extend()?;
+		Ok(author_ids.into_iter().map(|id| Author { id }).collect())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn date(text: &str) -> Option<NaiveDate> {
+		Some(text.parse().unwrap())
+	}
+
+	#[test]
+	fn paging() {
+		assert_eq!(limit_offset(None, None), (DEFAULT_PAGE_SIZE as i64, 0));
+		assert_eq!(limit_offset(Some(3), Some(20)), (20, 40));
+		// out of range pages and sizes are clamped
+		assert_eq!(limit_offset(Some(0), Some(0)), (1, 0));
+		assert_eq!(limit_offset(Some(2), Some(u32::MAX)), (MAX_PAGE_SIZE as i64, MAX_PAGE_SIZE as i64));
+	}
+
+	#[test]
+	fn history_days_are_inclusive() {
+		assert_eq!(history_days(date("2024-01-01"), date("2024-01-01")), 1);
+		assert_eq!(history_days(date("2024-01-01"), date("2024-01-31")), 31);
+		assert_eq!(history_days(date("2024-01-31"), date("2024-01-01")), 1);
+		assert_eq!(history_days(None, date("2024-01-01")), 4 * 365);
+		assert_eq!(history_days(Some(Utc::now().date_naive()), None), 1);
+	}
+}
@@ -1,9 +1,19 @@
 // define other modules
+mod admin;
 mod api_error;
 mod steamapi;
 mod cache;
+mod compression;
+mod conditional;
+mod request_id;
+mod name_cache;
+mod mod_types;
 mod api13;
 mod api14;
+mod mod_versions;
+mod v2;
+mod openapi;
+mod graphql;
 
 // import modules
 use crate::api_error::APIError;
@@ -11,14 +21,17 @@ use crate::api_error::APIError;
 // import libraries
 #[macro_use] extern crate rocket;
 extern crate reqwest;
+use admin::AdminState;
 use api13::Api13State;
 use api14::Api14State;
+use name_cache::NameCache;
+use cache::CacheFactory;
 use rocket::serde::json::{Value, serde_json};
 use rocket::response::content::RawHtml;
 use rocket::fs::FileServer;
 
 use chrono::Utc;
-use clokwerk::{AsyncScheduler, TimeUnits};
+use clokwerk::{AsyncScheduler, Job, TimeUnits};
 use rocket::tokio;
 use std::sync::Arc;
 use std::time::Duration;
@@ -26,11 +39,50 @@ use std::time::Duration;
 #[macro_export]
 macro_rules! cached_json {
     ($json:tt, $max_age:literal, $revalidate:literal) => {
-		Ok(CacheResponse::Public {
-			responder: serde_json::json!($json),
-			max_age: $max_age, // cached for seconds
-			must_revalidate: $revalidate,
-		})
+		match serde_json::to_value(&$json) {
+			Ok(json) => Ok(CacheResponse::Public {
+				responder: json,
+				max_age: $max_age, // cached for seconds
+				must_revalidate: $revalidate,
+			}),
+			Err(e) => Err($crate::api_error::serialize_error(e))
+		}
+	};
+}
+
+// cached_json for values which might be served past their ttl, see cache::Stale
+#[macro_export]
+macro_rules! stale_json {
+    ($json:tt, $stale_for:expr, $max_age:literal, $revalidate:literal) => {
+		match serde_json::to_value(&$json) {
+			Ok(json) => Ok($crate::cache::Stale {
+				responder: CacheResponse::Public {
+					responder: json,
+					max_age: $max_age,
+					must_revalidate: $revalidate,
+				},
+				stale_for: $stale_for,
+			}),
+			Err(e) => Err($crate::api_error::serialize_error(e))
+		}
+	};
+}
+
+// cached_json for values which only change when the db is synced
+#[macro_export]
+macro_rules! synced_json {
+    ($json:tt, $last_modified:expr, $max_age:literal, $revalidate:literal) => {
+		match serde_json::to_value(&$json) {
+			Ok(json) => Ok($crate::conditional::LastModified {
+				responder: CacheResponse::Public {
+					responder: json,
+					max_age: $max_age,
+					must_revalidate: $revalidate,
+				},
+				last_modified: $last_modified,
+			}),
+			Err(e) => Err($crate::api_error::serialize_error(e))
+		}
 	};
 }
 
@@ -40,6 +92,9 @@ fn index() -> RawHtml<&'static str>{
 		<h1>Index</h1>
 		<a href="/1.3">1.3</a><br>
 		<a href="/1.4">1.4</a><br>
+		<a href="/v2">v2</a><br>
+		<a href="/docs">docs</a> (<a href="/openapi.json">OpenAPI</a>)<br>
+		<a href="/graphql">graphql</a><br>
 		<a href="/img">img</a><br>
 		<a href="/version">version</a>
 	"#)
@@ -67,22 +122,84 @@ fn index_img() -> RawHtml<&'static str>{
 
 #[rocket::main]
 async fn main() -> Result<(), rocket::Error>{
+	// `tmlapis export-parquet [dir]` writes the parquet exports into dir instead of starting the server
+	#[cfg(feature = "parquet")]
+	if std::env::args().nth(1).as_deref() == Some("export-parquet") {
+		let dir = std::env::args().nth(2).unwrap_or_else(|| "export".to_owned());
+		let pool = api14::db::create_pool().await;
+		if let Err(e) = api14::parquet_api::write_dir(std::path::Path::new(&dir), &pool).await {
+			eprintln!("Export failed: {e}");
+			std::process::exit(1);
+		}
+		return Ok(());
+	}
+
 	let steam_api_key = Arc::new(std::env::var("STEAM_API_KEY").expect("the 'STEAM_API_KEY' environment variable could not be read"));
 	let pool = Arc::new(api14::db::create_pool().await);
 
-	let api13_state = Api13State::init(Arc::clone(&steam_api_key));
-	let api14_state = Api14State::init(Arc::clone(&steam_api_key), Arc::clone(&pool));
+	// `tmlapis archive-1.3` scrapes everything the 1.3 server has into the archive instead of starting the server
+	if std::env::args().nth(1).as_deref() == Some("archive-1.3") {
+		if let Err(e) = api13::archive::archive_all(&pool, &steam_api_key).await {
+			eprintln!("Archiving failed: {e}");
+			std::process::exit(1);
+		}
+		return Ok(());
+	}
+
+	let name_cache = Arc::new(NameCache::new(Arc::clone(&pool)));
+
+	let caches = CacheFactory::from_env().await;
+	let api13_state = Api13State::init(Arc::clone(&steam_api_key), Arc::clone(&pool), Arc::clone(&name_cache), &caches);
+	let api14_state = Api14State::init(Arc::clone(&steam_api_key), Arc::clone(&pool), Arc::clone(&name_cache), &caches);
+	let admin_state = AdminState::init([api13_state.caches(), api14_state.caches()].concat());
+
+	// build the mod list snapshot before the first request needs it
+	let list_snapshot = Arc::clone(&api14_state.list_snapshot);
+	{
+		let pool = Arc::clone(&pool);
+		let list_snapshot = Arc::clone(&list_snapshot);
+		tokio::spawn(async move {
+			if let Err(e) = list_snapshot.rebuild(&pool).await {
+				log::error!("Could not build the mod list snapshot: {e}");
+			}
+		});
+	}
 
 	let mut scheduler = AsyncScheduler::with_tz(Utc);
 
+	// archiving takes hours, spawn it so it doesn't hold up the db schedule
+	{
+		let pool = Arc::clone(&pool);
+		let steam_api_key = Arc::clone(&steam_api_key);
+		scheduler.every(1.day()).at("03:00").run(move || {
+			let pool = Arc::clone(&pool);
+			let steam_api_key = Arc::clone(&steam_api_key);
+			async move {
+				tokio::spawn(async move {
+					if let Err(e) = api13::archive::archive_all(&pool, &steam_api_key).await {
+						log::error!("Could not archive 1.3: {e}");
+					}
+				});
+			}
+		});
+	}
+
 	scheduler.every(2.hour()).run(move || {
 		let pool = Arc::clone(&pool);
 		let steam_api_key = Arc::clone(&steam_api_key);
+		let name_cache = Arc::clone(&name_cache);
+		let list_snapshot = Arc::clone(&list_snapshot);
 
 		async move {
 			log::info!("Running DB schedule");
-			if let Err(e) = api14::db::update_db(&pool, &steam_api_key).await {
-				log::error!("Could not update mod history: {e}");
+			match api14::db::update_db(&pool, &steam_api_key).await {
+				Ok(()) => if let Err(e) = list_snapshot.rebuild(&pool).await {
+					log::error!("Could not rebuild the mod list snapshot: {e}");
+				},
+				Err(e) => log::error!("Could not update mod history: {e}")
+			}
+			if let Err(e) = name_cache.remove_expired().await {
+				log::error!("Could not clean up the name cache: {e}");
 			}
 			log::info!("Finished DB schedule");
 		}
@@ -96,12 +213,23 @@ async fn main() -> Result<(), rocket::Error>{
 	});
 
 	// use variable to get info like config or routes
-	let _ = rocket::build().manage(api14_state).manage(api13_state)
-		.mount("/", routes![index, version])
+	let mut rocket = rocket::build().attach(request_id::RequestIds).attach(conditional::ConditionalRequests).attach(compression::Compression).manage(api14_state).manage(api13_state).manage(admin_state).manage(graphql::schema())
+		.register("/", catchers![api_error::default_catcher])
+		.mount("/", routes![index, version, mod_versions::mod_versions])
+		.mount("/", openapi::get_routes())
+		.mount("/", graphql::get_routes())
+		.mount("/admin/", admin::get_routes())
 		.mount("/1.3/", api13::get_routes())
 		.mount("/1.4/", api14::get_routes())
+		.mount("/v2/", v2::get_routes())
 		.mount("/img/", FileServer::from("./img/"))
-		.mount("/img/", routes![index_img])
+		.mount("/img/", routes![index_img]);
+
+	if let Some(dir) = api13::icons::MIRROR_DIR.as_ref() {
+		rocket = rocket.mount(api13::icons::ROUTE, FileServer::from(dir));
+	}
+
+	let _ = rocket
 		.ignite().await?
 		.launch().await?; // blocking
 
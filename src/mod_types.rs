@@ -0,0 +1,48 @@
+// types the 1.3 and 1.4 mods have in common, shared by their apis and /v2
+use std::fmt::Display;
+use std::str::FromStr;
+use rocket::serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default, Debug, JsonSchema)]
+#[serde(crate = "rocket::serde")]
+pub enum ModSide {
+	#[default]
+	Both,
+	Client,
+	Server,
+	NoSync
+}
+
+impl Display for ModSide {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(match self {
+			ModSide::Both => "Both",
+			ModSide::Client => "Client",
+			ModSide::Server => "Server",
+			ModSide::NoSync => "NoSync"
+		})
+	}
+}
+
+impl FromStr for ModSide {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.to_ascii_lowercase().as_str() {
+			"both" => Ok(ModSide::Both),
+			"client" => Ok(ModSide::Client),
+			"server" => Ok(ModSide::Server),
+			"nosync" => Ok(ModSide::NoSync),
+			_ => Err(format!("unknown modside '{s}'"))
+		}
+	}
+}
+
+// a mod another mod depends on, with the version it needs if there is one
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct ModReference {
+	pub name: String,
+	pub version: Option<String>
+}
@@ -0,0 +1,169 @@
+// mods on the 1.3 mod browser and the 1.4 workshop are matched by internal name
+use std::cmp::Reverse;
+use rocket::State;
+use rocket::serde::Serialize;
+use schemars::JsonSchema;
+use rocket::serde::json::{serde_json, Value};
+use rocket_cache_response::CacheResponse;
+use crate::{cached_json, steamapi, APIError};
+use crate::api_error::ErrorResponse;
+use crate::api13::{self, Api13State, responses::ModHistory};
+use crate::api14::{db::{self, ModFilter}, responses::ModVersion, Api14State};
+
+// the mod on the 1.3 mod browser
+#[derive(Serialize, JsonSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct LegacyMod {
+	pub display_name: String,
+	pub version: String,
+	pub downloads_total: u32,
+	pub versions: Vec<ModHistory>,
+	// every tModLoader version a version of the mod was published for, newest first
+	pub tmodloader_versions: Vec<String>,
+}
+
+// the mod on the 1.4 workshop
+#[derive(Serialize, JsonSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct CurrentMod {
+	pub mod_id: String,
+	pub display_name: String,
+	pub downloads_total: u32,
+	pub versions: Vec<ModVersion>,
+	pub tmodloader_versions: Vec<String>,
+}
+
+#[derive(Serialize, JsonSchema)]
+#[serde(crate = "rocket::serde", rename_all = "snake_case")]
+pub enum PortStatus {
+	// on both the 1.3 mod browser and the 1.4 workshop
+	Ported,
+	LegacyOnly,
+	CurrentOnly,
+	// one of them couldn't be looked up, see legacy_error and current_error
+	Unknown,
+}
+
+#[derive(Serialize, JsonSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct ModVersions {
+	pub internal_name: String,
+	pub port_status: PortStatus,
+	// 1.3 and 1.4 downloads together
+	pub downloads_total: u64,
+	pub legacy: Option<LegacyMod>,
+	pub current: Option<CurrentMod>,
+	// why the mod couldn't be looked up on the 1.3 mod browser, legacy is null then
+	pub legacy_error: Option<ErrorResponse>,
+	pub current_error: Option<ErrorResponse>,
+}
+
+// unique, in the order they were first seen
+fn distinct<'a>(versions: impl Iterator<Item = &'a String>) -> Vec<String> {
+	let mut distinct: Vec<String> = Vec::new();
+	for version in versions {
+		if !distinct.contains(version) {
+			distinct.push(version.clone());
+		}
+	}
+	distinct
+}
+
+// None if the mod isn't on the 1.3 mod browser
+async fn legacy_mod(internal_name: &str, state: &Api13State) -> Result<Option<LegacyMod>, APIError> {
+	let info = match api13::api::get_mod_info(internal_name, state).await {
+		Ok((info, _)) => info,
+		Err(APIError::ModNameNotFound(_)) => return Ok(None),
+		Err(e) => return Err(e)
+	};
+	// the versions are only extra detail, the mod is still shown without them
+	let mut history = match api13::api::get_history(internal_name, state).await {
+		Ok(history) => history,
+		Err(e) => {
+			log::warn!("Could not get the 1.3 history of '{internal_name}': {e}");
+			Vec::new()
+		}
+	};
+	history.sort_by_key(|h| Reverse(h.publish_date));
+
+	let tmodloader_versions = distinct(std::iter::once(&info.modloaderversion).chain(history.iter().map(|h| &h.tmodloader_version)));
+	Ok(Some(LegacyMod {
+		display_name: info.displayname,
+		version: info.version,
+		downloads_total: info.downloads,
+		versions: history,
+		tmodloader_versions,
+	}))
+}
+
+fn split<T>(side: &str, internal_name: &str, result: Result<Option<T>, APIError>) -> (Option<T>, Option<APIError>) {
+	match result {
+		Ok(found) => (found, None),
+		Err(e) => {
+			log::warn!("Could not look up '{internal_name}' on {side}: {e}");
+			(None, Some(e))
+		}
+	}
+}
+
+// None if the mod isn't on the 1.4 workshop. If more than one mod has the name, the most downloaded one is used
+async fn current_mod(internal_name: &str, state: &Api14State) -> Result<Option<CurrentMod>, APIError> {
+	let filter = ModFilter {
+		internal_name: Some(internal_name.to_owned()),
+		sort: Some("downloads"),
+		descending: true,
+		limit: Some(1),
+		..ModFilter::default()
+	};
+	let Some(info) = db::query_mods(&filter, &state.db).await?.pop() else {
+		return Ok(None);
+	};
+
+	Ok(Some(CurrentMod {
+		mod_id: info.mod_id.to_string(),
+		display_name: info.display_name,
+		downloads_total: info.downloads_total,
+		tmodloader_versions: distinct(info.versions.iter().map(|v| &v.tmodloader_version)),
+		versions: info.versions,
+	}))
+}
+
+#[get("/mod/<internal_name>/versions")]
+pub async fn mod_versions(internal_name: &str, api13_state: &State<Api13State>, api14_state: &State<Api14State>) -> Result<CacheResponse<Value>, APIError> {
+	let internal_name = steamapi::validate_modname(internal_name)?;
+
+	// a side which fails is left out, so the 1.3 server being down doesn't hide the mod on 1.4 and the other way around
+	let (legacy, current) = rocket::tokio::join!(
+		legacy_mod(internal_name, api13_state),
+		current_mod(internal_name, api14_state)
+	);
+	let (legacy, legacy_error) = split("1.3", internal_name, legacy);
+	let (current, current_error) = split("1.4", internal_name, current);
+
+	let port_status = match (&legacy, &current) {
+		(Some(_), Some(_)) => PortStatus::Ported,
+		// if a side failed there's nothing to show, and whether the mod exists isn't known
+		(None, None) => return Err(legacy_error.or(current_error).unwrap_or_else(|| APIError::ModNameNotFound(internal_name.to_owned()))),
+		_ if legacy_error.is_some() || current_error.is_some() => PortStatus::Unknown,
+		(Some(_), None) => PortStatus::LegacyOnly,
+		(None, Some(_)) => PortStatus::CurrentOnly,
+	};
+
+	let versions = ModVersions {
+		internal_name: internal_name.to_owned(),
+		port_status,
+		downloads_total: legacy.as_ref().map_or(0, |m| m.downloads_total as u64)
+			+ current.as_ref().map_or(0, |m| m.downloads_total as u64),
+		legacy,
+		current,
+		legacy_error: legacy_error.as_ref().map(ErrorResponse::from),
+		current_error: current_error.as_ref().map(ErrorResponse::from),
+	};
+
+	// retried soon if a side failed
+	if versions.legacy_error.is_some() || versions.current_error.is_some() {
+		cached_json!(versions, 60, false)
+	} else {
+		cached_json!(versions, 3600, false)
+	}
+}
@@ -0,0 +1,122 @@
+use std::sync::Arc;
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+use crate::{APIError, steamapi};
+
+// how long a resolved name is trusted, vanity names can be given up and claimed by someone else
+const RESOLVED_TTL: i64 = 24 * 3600;
+// unresolvable names are only remembered briefly so that new mods and renamed profiles show up soon
+const UNRESOLVED_TTL: i64 = 600;
+
+const STEAM_NAME: &str = "steam";
+const MOD_NAME: &str = "mod";
+
+// name -> id mappings shared by the 1.3 and 1.4 apis, stored in the database so they survive restarts.
+// the cache only saves steam requests, if the database can't be read or written the name is looked up anyway
+pub struct NameCache {
+	db: Arc<PgPool>,
+}
+
+impl NameCache {
+	pub fn new(db: Arc<PgPool>) -> NameCache {
+		NameCache { db }
+	}
+
+	pub async fn steam_id(&self, steamname: &str, api_key: &str) -> Result<u64, APIError> {
+		// names which can't be vanity urls would only fill up the cache
+		if !steamapi::is_vanity_name(steamname) {
+			return Err(APIError::SteamNameNotResolveable(steamname.to_owned()));
+		}
+		// vanity urls are case insensitive
+		let key = steamname.to_lowercase();
+		match self.get(STEAM_NAME, &key).await {
+			Some(Some(steamid)) => Ok(steamid),
+			Some(None) => Err(APIError::SteamNameNotResolveable(steamname.to_owned())),
+			None => {
+				let result = steamapi::steamname_to_steamid(steamname, api_key).await;
+				self.remember(STEAM_NAME, &key, &result, |e| matches!(e, APIError::SteamNameNotResolveable(_))).await;
+				result
+			}
+		}
+	}
+
+	pub async fn mod_id(&self, modname: &str, api_key: &str) -> Result<u64, APIError> {
+		let modname = steamapi::validate_modname(modname)?;
+		match self.get(MOD_NAME, modname).await {
+			Some(Some(modid)) => Ok(modid),
+			Some(None) => Err(APIError::ModNameNotFound(modname.to_owned())),
+			None => {
+				let result = steamapi::modname_to_modid(modname, api_key).await;
+				self.remember(MOD_NAME, modname, &result, |e| matches!(e, APIError::ModNameNotFound(_))).await;
+				result
+			}
+		}
+	}
+
+	// returns None if the name isn't cached, Some(None) if it's cached as unresolvable
+	async fn get(&self, kind: &str, name: &str) -> Option<Option<u64>> {
+		let row = sqlx::query!(
+			r#"
+			SELECT id, resolved_at
+			FROM name_cache
+			WHERE kind = $1 AND name = $2
+			"#,
+			kind,
+			name
+		)
+		.fetch_optional(&*self.db)
+		.await
+		.unwrap_or_else(|e| {
+			log::warn!("Could not read the name cache: {e}");
+			None
+		});
+
+		row.and_then(|row| {
+			let ttl = if row.id.is_some() { RESOLVED_TTL } else { UNRESOLVED_TTL };
+			(Utc::now() - row.resolved_at < Duration::seconds(ttl)).then_some(row.id.map(|id| id as u64))
+		})
+	}
+
+	// stores successful lookups and lookups that failed because the name doesn't exist, but not network errors
+	async fn remember(&self, kind: &str, name: &str, result: &Result<u64, APIError>, is_unresolvable: impl Fn(&APIError) -> bool) {
+		let id = match result {
+			Ok(id) => Some(*id as i64),
+			Err(e) if is_unresolvable(e) => None,
+			Err(_) => return
+		};
+
+		let written = sqlx::query!(
+			r#"
+			INSERT INTO name_cache (kind, name, id)
+			VALUES ($1, $2, $3)
+			ON CONFLICT (kind, name) DO UPDATE SET
+				id = EXCLUDED.id,
+				resolved_at = NOW()
+			"#,
+			kind,
+			name,
+			id
+		)
+		.execute(&*self.db)
+		.await;
+
+		if let Err(e) = written {
+			log::warn!("Could not write '{name}' to the name cache: {e}");
+		}
+	}
+
+	pub async fn remove_expired(&self) -> Result<(), APIError> {
+		sqlx::query!(
+			r#"
+			DELETE FROM name_cache
+			WHERE resolved_at < NOW() - make_interval(secs => CASE WHEN id IS NULL THEN $1::FLOAT8 ELSE $2::FLOAT8 END)
+			"#,
+			UNRESOLVED_TTL as f64,
+			RESOLVED_TTL as f64
+		)
+		.execute(&*self.db)
+		.await?;
+
+		Ok(())
+	}
+}
@@ -0,0 +1,317 @@
+// the OpenAPI document is built from the routes of the api modules and the types they respond with,
+// so it can't drift from the code. Served at /openapi.json with a Swagger UI at /docs
+use std::collections::HashMap;
+use std::sync::LazyLock;
+use rocket::Route;
+use rocket::response::content::{RawHtml, RawJson};
+use rocket::serde::json::{serde_json::{json, Map}, Value};
+use schemars::{generate::SchemaSettings, JsonSchema, SchemaGenerator};
+use crate::{api13, api14, mod_versions, v2};
+use crate::api_error::ErrorResponse;
+
+static SPEC: LazyLock<String> = LazyLock::new(|| build().to_string());
+
+// what a route responds with
+enum Body {
+	Json(Value),
+	File(&'static str),
+}
+
+struct Section {
+	base: &'static str,
+	tag: &'static str,
+	// keeps the operation ids unique, v2 reuses the names of the 1.3 and 1.4 routes
+	id_prefix: &'static str,
+	routes: Vec<Route>,
+	// the body of a route by its name, the tests check that every route is listed
+	body: fn(&str, &mut SchemaGenerator) -> Option<Body>,
+	// the fields of the struct a route collects its query into with <query..>
+	query: fn(&str, &mut SchemaGenerator) -> Option<Value>,
+}
+
+fn json<T: JsonSchema>(generator: &mut SchemaGenerator) -> Option<Body> {
+	Some(Body::Json(generator.subschema_for::<T>().to_value()))
+}
+
+fn api13_body(name: &str, generator: &mut SchemaGenerator) -> Option<Body> {
+	use api13::responses::*;
+	match name {
+		"count_1_3" => Some(Body::Json(json!({
+			"type": "object",
+			"properties": { "total": { "type": "integer" } },
+			"required": ["total"]
+		}))),
+		"mod_1_3" => json::<ModInfo>(generator),
+		"author_1_3" | "author_1_3_str" => json::<AuthorInfo>(generator),
+		"list_1_3" => json::<Vec<ModListInfo>>(generator),
+		"history_1_3" => json::<Vec<ModHistory>>(generator),
+		_ => None
+	}
+}
+
+fn api13_query(name: &str, generator: &mut SchemaGenerator) -> Option<Value> {
+	match name {
+		"list_1_3" => Some(api13::api::ModListQuery::json_schema(generator).to_value()),
+		_ => None
+	}
+}
+
+fn api14_body(name: &str, generator: &mut SchemaGenerator) -> Option<Body> {
+	use api14::responses::*;
+	match name {
+		"count_1_4" => Some(Body::Json(json!({ "type": "integer" }))),
+		"author_1_4" | "author_1_4_str" => json::<AuthorInfo>(generator),
+		"author_profile_1_4" | "author_profile_1_4_str" => json::<AuthorProfile>(generator),
+		"mod_1_4" | "mod_1_4_str" => json::<ModInfo>(generator),
+		"list_1_4" => json::<Vec<ModInfo>>(generator),
+		"list_authors" => json::<Vec<ListedAuthor>>(generator),
+		"history_mod" | "history_mod_str" | "history_author" | "history_author_str" => json::<Vec<ModHistoryEntry>>(generator),
+		"history_global" => json::<Vec<GlobalHistoryEntry>>(generator),
+		// keyed by the requested steamid
+		"get_steam_avatar" => json::<HashMap<String, AvatarEntry>>(generator),
+		"export_mods_ndjson" | "export_history_ndjson" => Some(Body::File("application/x-ndjson")),
+		"export_mods_csv" | "export_history_csv" => Some(Body::File("text/csv")),
+		"export_mods_parquet" | "export_history_parquet" => Some(Body::File("application/vnd.apache.parquet")),
+		#[cfg(feature = "parquet")]
+		"export_history_months" => json::<Vec<HistoryMonth>>(generator),
+		_ => None
+	}
+}
+
+fn api14_query(name: &str, generator: &mut SchemaGenerator) -> Option<Value> {
+	match name {
+		"list_1_4" => Some(api14::list_api::ListQuery::json_schema(generator).to_value()),
+		_ => None
+	}
+}
+
+fn v2_body(name: &str, generator: &mut SchemaGenerator) -> Option<Body> {
+	match name {
+		"mod_1_3" | "mod_1_4" | "mod_1_4_str" => json::<v2::schema::Mod>(generator),
+		_ => None
+	}
+}
+
+fn root_body(name: &str, generator: &mut SchemaGenerator) -> Option<Body> {
+	match name {
+		"mod_versions" => json::<mod_versions::ModVersions>(generator),
+		_ => None
+	}
+}
+
+fn no_query(_: &str, _: &mut SchemaGenerator) -> Option<Value> {
+	None
+}
+
+// rocket doesn't keep the argument types of a handler, so the parameters of all routes are listed here
+// by name. The tests check that none is missing
+fn param_schema(name: &str, generator: &mut SchemaGenerator) -> Option<Value> {
+	let schema = match name {
+		"steamid" | "modid" => generator.subschema_for::<u64>(),
+		"steamids" => generator.subschema_for::<Vec<u64>>(),
+		"steamname" | "modname" | "internal_name" | "file" => generator.subschema_for::<String>(),
+		// dates like 2024-01-31
+		"from" | "to" => generator.subschema_for::<chrono::NaiveDate>(),
+		_ => return None
+	};
+	Some(schema.to_value())
+}
+
+fn param(name: &str, location: &str, schema: Value) -> Value {
+	json!({
+		"name": name,
+		"in": location,
+		"required": location == "path",
+		"schema": schema
+	})
+}
+
+// "<name>" to "name", None for static segments
+fn dynamic(segment: &str) -> Option<&str> {
+	segment.strip_prefix('<')?.strip_suffix('>')
+}
+
+fn responses(body: Option<Body>, error: &Value) -> Value {
+	let content = match body {
+		Some(Body::Json(schema)) => json!({ "application/json": { "schema": schema } }),
+		Some(Body::File(media_type)) => json!({ media_type: { "schema": { "type": "string", "format": "binary" } } }),
+		None => json!({ "application/json": { "schema": {} } })
+	};
+	json!({
+		"200": { "description": "OK", "content": content },
+		"default": { "description": "Error", "content": { "application/json": { "schema": error } } }
+	})
+}
+
+fn sections() -> [Section; 4] {
+	[
+		Section { base: "/1.3", tag: "1.3", id_prefix: "", routes: api13::get_routes(), body: api13_body, query: api13_query },
+		Section { base: "/1.4", tag: "1.4", id_prefix: "", routes: api14::get_routes(), body: api14_body, query: api14_query },
+		Section { base: "/v2", tag: "v2", id_prefix: "v2_", routes: v2::get_routes(), body: v2_body, query: no_query },
+		Section { base: "", tag: "1.3 and 1.4", id_prefix: "", routes: routes![mod_versions::mod_versions], body: root_body, query: no_query },
+	]
+}
+
+// the html pages are for browsers
+fn is_documented(route: &Route) -> bool {
+	!route.name.as_deref().unwrap_or_default().starts_with("index")
+}
+
+// routes which only differ by the type of a path parameter (like a steamid and a steam name) are
+// ranked by rocket, here they're one operation and the parameter accepts either
+fn build() -> Value {
+	let mut generator = SchemaSettings::openapi3().for_serialize().into_generator();
+	let error = generator.subschema_for::<ErrorResponse>().to_value();
+
+	let mut paths = Map::new();
+	for section in sections() {
+		// the path each normalized path was first documented under
+		let mut seen: HashMap<String, String> = HashMap::new();
+
+		for route in &section.routes {
+			let name = route.name.as_deref().unwrap_or_default();
+			if !is_documented(route) {
+				continue;
+			}
+
+			let segments: Vec<&str> = route.uri.path().split('/').filter(|s| !s.is_empty()).collect();
+			let normalized = segments.iter().map(|s| if dynamic(s).is_some() { "{}" } else { s }).collect::<Vec<_>>().join("/");
+
+			if let Some(path) = seen.get(&normalized) {
+				let params = paths[path.as_str()]["get"]["parameters"].as_array_mut().into_iter().flatten();
+				let names = segments.iter().filter_map(|s| dynamic(s));
+				for (param, name) in params.filter(|p| p["in"] == "path").zip(names) {
+					if param["name"] != name {
+						param["schema"] = generator.subschema_for::<String>().to_value();
+						param["description"] = Value::String(format!("{} or {name}", param["name"].as_str().unwrap_or_default()));
+					}
+				}
+				continue;
+			}
+
+			let mut parameters = Vec::new();
+			let mut path = section.base.to_owned();
+			for segment in &segments {
+				match dynamic(segment) {
+					Some(param_name) => {
+						path.push_str(&format!("/{{{param_name}}}"));
+						let schema = param_schema(param_name, &mut generator).unwrap_or_else(|| json!({}));
+						parameters.push(param(param_name, "path", schema));
+					}
+					None => {
+						path.push('/');
+						path.push_str(segment);
+					}
+				}
+			}
+			if path.is_empty() {
+				path.push('/');
+			}
+
+			for segment in route.uri.query().into_iter().flat_map(|q| q.split('&')) {
+				let Some(param_name) = dynamic(segment) else { continue };
+				if param_name.ends_with("..") {
+					let query = (section.query)(name, &mut generator).unwrap_or_default();
+					let fields = query["properties"].as_object().into_iter().flatten();
+					parameters.extend(fields.map(|(field, schema)| param(field, "query", schema.clone())));
+				} else {
+					let schema = param_schema(param_name, &mut generator).unwrap_or_else(|| json!({}));
+					parameters.push(param(param_name, "query", schema));
+				}
+			}
+
+			let body = (section.body)(name, &mut generator);
+			paths.insert(path.clone(), json!({
+				"get": {
+					"operationId": format!("{}{name}", section.id_prefix),
+					"tags": [section.tag],
+					"parameters": parameters,
+					"responses": responses(body, &error)
+				}
+			}));
+			seen.insert(normalized, path);
+		}
+	}
+
+	json!({
+		"openapi": "3.0.3",
+		"info": {
+			"title": "tMLAPIs",
+			"version": env!("CARGO_PKG_VERSION"),
+			"description": "Mods and authors of the tModLoader 1.3 mod browser and the 1.4 steam workshop"
+		},
+		"paths": paths,
+		"components": { "schemas": generator.take_definitions(true) }
+	})
+}
+
+#[get("/openapi.json")]
+fn openapi() -> RawJson<&'static str> {
+	RawJson(SPEC.as_str())
+}
+
+#[get("/docs")]
+fn docs() -> RawHtml<&'static str> {
+	RawHtml(r##"
+		<!DOCTYPE html>
+		<html>
+		<head>
+			<title>tMLAPIs docs</title>
+			<link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css">
+		</head>
+		<body>
+			<div id="swagger-ui"></div>
+			<script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
+			<script>
+				SwaggerUIBundle({ url: "/openapi.json", dom_id: "#swagger-ui" });
+			</script>
+		</body>
+		</html>
+	"##)
+}
+
+pub fn get_routes() -> Vec<Route> {
+	routes![openapi, docs]
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn documented_routes() -> Vec<(Section, Vec<Route>)> {
+		sections().into_iter().map(|section| {
+			let routes = section.routes.iter().filter(|route| is_documented(route)).cloned().collect();
+			(section, routes)
+		}).collect()
+	}
+
+	#[test]
+	fn every_route_has_a_typed_body() {
+		let mut generator = SchemaSettings::openapi3().for_serialize().into_generator();
+		for (section, routes) in documented_routes() {
+			for route in routes {
+				let name = route.name.as_deref().unwrap_or_default();
+				assert!((section.body)(name, &mut generator).is_some(), "{} {name} has no response type", section.tag);
+			}
+		}
+
+		let spec = build();
+		for (path, item) in spec["paths"].as_object().unwrap() {
+			let content = item["get"]["responses"]["200"]["content"].as_object().unwrap();
+			assert!(content.values().all(|media| media["schema"] != json!({})), "{path} responds with untyped json");
+		}
+	}
+
+	#[test]
+	fn every_parameter_has_a_type() {
+		let mut generator = SchemaSettings::openapi3().for_serialize().into_generator();
+		for (_, routes) in documented_routes() {
+			for route in routes {
+				let segments = route.uri.path().split('/').chain(route.uri.query().into_iter().flat_map(|q| q.split('&')));
+				for name in segments.filter_map(dynamic).filter(|name| !name.ends_with("..")) {
+					assert!(param_schema(name, &mut generator).is_some(), "'{name}' of {} has no type", route.uri);
+				}
+			}
+		}
+	}
+}
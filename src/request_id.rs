@@ -0,0 +1,54 @@
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::sync::LazyLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::Header;
+use rocket::{Request, Response};
+
+// random per process so the ids of multiple instances don't collide
+static PREFIX: LazyLock<u32> = LazyLock::new(|| RandomState::new().hash_one("request_id") as u32);
+static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+// identifies a request in error bodies, the X-Request-Id header and the logs
+pub struct RequestId(String);
+
+impl RequestId {
+	// reuses the id a proxy in front of us set, otherwise a new one is made on first use
+	pub fn of<'r>(req: &'r Request<'_>) -> &'r RequestId {
+		req.local_cache(|| {
+			let id = req.headers().get_one("X-Request-Id")
+				.filter(|id| !id.is_empty() && id.len() <= 64 && id.chars().all(|c| c.is_ascii_graphic()))
+				.map(str::to_owned)
+				.unwrap_or_else(|| format!("{:08x}-{:08x}", *PREFIX, COUNTER.fetch_add(1, Ordering::Relaxed)));
+			RequestId(id)
+		})
+	}
+}
+
+impl std::fmt::Display for RequestId {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(&self.0)
+	}
+}
+
+// sends the request id with every response
+pub struct RequestIds;
+
+#[rocket::async_trait]
+impl Fairing for RequestIds {
+	fn info(&self) -> Info {
+		Info {
+			name: "Request ids",
+			kind: Kind::Response
+		}
+	}
+
+	async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+		let id = RequestId::of(req);
+		if res.status().class().is_server_error() {
+			log::warn!("Request {id} failed with {}", res.status());
+		}
+		res.set_header(Header::new("X-Request-Id", id.to_string()));
+	}
+}
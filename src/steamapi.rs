@@ -2,8 +2,10 @@ extern crate reqwest;
 use std::collections::HashMap;
 
 use rocket::serde::{Deserialize, DeserializeOwned, Serialize};
-use rocket::serde::json::serde_json::Value;
+use rocket::serde::json::serde_json::{self, Value};
+use schemars::JsonSchema;
 use crate::APIError;
+use crate::api_error::{check_upstream, serialize_error};
 
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(crate = "rocket::serde")]
@@ -58,7 +60,7 @@ pub struct PublishedFileID {
 	pub publishedfileid: String
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(crate = "rocket::serde")]
 #[allow(dead_code)]
 pub struct PublishedFileDetails {
@@ -130,7 +132,7 @@ pub struct Child {
 	pub file_type: u32
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 #[serde(crate = "rocket::serde")]
 pub struct VoteData {
 	pub score: f64,
@@ -138,14 +140,14 @@ pub struct VoteData {
 	pub votes_down: u32
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(crate = "rocket::serde")]
 pub struct KVTag {
 	pub key: String,
 	pub value: String
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 #[serde(crate = "rocket::serde")]
 pub struct ModTag {
 	pub tag: String,
@@ -200,77 +202,158 @@ fn check_missing_fields(files: &Vec<PublishedFileDetails>) {
 
 const STEAM_API_URL: &str = "https://api.steampowered.com";
 pub const APP_ID: &str = "1281930";
+// GetPlayerSummaries ignores every steamid after the first 100
+pub const MAX_STEAMIDS_PER_REQUEST: usize = 100;
 
-// does a get reqwests on the specified URL and Returns a Json<String> if successful or a Status if it errored
-async fn get_steam<T: DeserializeOwned>(url: &str) -> Result<T, APIError> {
-	let res = reqwest::get(format!("{STEAM_API_URL}{url}")).await?;
-	log::debug!("Requesting SteamAPI at: {STEAM_API_URL}{url}");
-	Ok(res.json::<Response<T>>().await?.response)
+// does a get reqwest on the specified path with the serialized query and returns the deserialized response
+async fn get_steam<T: DeserializeOwned>(path: &str, query: &impl Serialize) -> Result<T, APIError> {
+	get_steam_with(&reqwest::Client::new(), path, query).await
 }
 
-pub async fn get_mod_count(api_key: &str) -> Result<CountResponse, APIError> {
-	let url = format!("/IPublishedFileService/QueryFiles/v1/?key={}&appid={APP_ID}&totalonly=true", api_key);
-	Ok(get_steam::<CountResponse>(&url).await?)
+async fn get_steam_with<T: DeserializeOwned>(client: &reqwest::Client, path: &str, query: &impl Serialize) -> Result<T, APIError> {
+	log::debug!("Requesting SteamAPI at: {STEAM_API_URL}{path}");
+	let res = client.get(format!("{STEAM_API_URL}{path}")).query(query).send().await?;
+	Ok(check_upstream(res)?.json::<Response<T>>().await?.response)
 }
 
-pub async fn get_user_mods(steamid: u64, api_key: &str) -> Result<ModListResponse, APIError> {
-	let url = format!("/IPublishedFileService/GetUserFiles/v1/?key={}&appid={APP_ID}&steamid={}&numperpage=100&return_short_description=false&return_children=true", api_key, steamid);
-	let res = get_steam::<ModListResponse>(&url).await?;
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct QueryFilesQuery<'a> {
+	key: &'a str,
+	appid: &'a str,
+	#[serde(skip_serializing_if = "Option::is_none")] totalonly: Option<bool>,
+	#[serde(skip_serializing_if = "Option::is_none")] input_json: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")] cursor: Option<&'a str>,
+	#[serde(skip_serializing_if = "Option::is_none")] numperpage: Option<u32>,
+	#[serde(skip_serializing_if = "Option::is_none")] cache_max_age_seconds: Option<u32>,
+	#[serde(skip_serializing_if = "Option::is_none")] return_details: Option<bool>,
+	#[serde(skip_serializing_if = "Option::is_none")] return_kv_tags: Option<bool>,
+	#[serde(skip_serializing_if = "Option::is_none")] return_children: Option<bool>,
+	#[serde(skip_serializing_if = "Option::is_none")] return_tags: Option<bool>,
+	#[serde(skip_serializing_if = "Option::is_none")] return_vote_data: Option<bool>,
+}
 
-	if let Some(files) = res.publishedfiledetails.as_ref() {
-		check_missing_fields(files);
+impl<'a> QueryFilesQuery<'a> {
+	fn new(api_key: &'a str) -> QueryFilesQuery<'a> {
+		QueryFilesQuery {
+			key: api_key,
+			appid: APP_ID,
+			totalonly: None,
+			input_json: None,
+			cursor: None,
+			numperpage: None,
+			cache_max_age_seconds: None,
+			return_details: None,
+			return_kv_tags: None,
+			return_children: None,
+			return_tags: None,
+			return_vote_data: None,
+		}
 	}
-	
-	Ok(res)
+}
+
+// QueryFiles only supports filtering by kvtags through its json input
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct QueryFilesInput<'a> {
+	required_kv_tags: Vec<KVTagFilter<'a>>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct KVTagFilter<'a> {
+	key: &'a str,
+	value: &'a str,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct GetDetailsQuery<'a> {
+	key: &'a str,
+	appid: &'a str,
+	#[serde(rename = "publishedfileids[0]")] publishedfileid: u64,
+	includekvtags: bool,
+	includechildren: bool,
+	includetags: bool,
+	includevotes: bool,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct ResolveVanityURLQuery<'a> {
+	key: &'a str,
+	vanityurl: &'a str,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct GetPlayerSummariesQuery<'a> {
+	key: &'a str,
+	steamids: String,
+}
+
+pub async fn get_mod_count(api_key: &str) -> Result<CountResponse, APIError> {
+	let query = QueryFilesQuery {
+		totalonly: Some(true),
+		..QueryFilesQuery::new(api_key)
+	};
+	get_steam("/IPublishedFileService/QueryFiles/v1/", &query).await
 }
 
 pub async fn get_mod_info(modid: u64 , api_key: &str) -> Result<PublishedFileDetails, APIError> {
-	let url = format!("/IPublishedFileService/GetDetails/v1/?key={api_key}&appid={APP_ID}\
-		&publishedfileids%5B0%5D={modid}\
-		&includekvtags=true\
-		&includechildren=true\
-		&includetags=true\
-		&includevotes=true"
-	);
-	let res = get_steam::<ModResponse>(&url).await?;
+	let query = GetDetailsQuery {
+		key: api_key,
+		appid: APP_ID,
+		publishedfileid: modid,
+		includekvtags: true,
+		includechildren: true,
+		includetags: true,
+		includevotes: true,
+	};
+	let res = get_steam::<ModResponse>("/IPublishedFileService/GetDetails/v1/", &query).await?;
 	
-	match res.publishedfiledetails[0].clone() {
-		SteamResult::Ok(pfd) => {
+	match res.publishedfiledetails.into_iter().next() {
+		Some(SteamResult::Ok(pfd)) => {
 			if !pfd.extra.is_empty() {
-				log::warn!("Unknown fields at mod[{}]: {:?}", pfd.publishedfileid.clone().unwrap(), pfd.extra.keys());
+				log::warn!("Unknown fields at mod[{}]: {:?}", pfd.publishedfileid.clone().unwrap_or_default(), pfd.extra.keys());
 			}
 			Ok(pfd)
 		},
-		SteamResult::Err(_) => Err(APIError::InvalidModID(modid))
+		_ => Err(APIError::InvalidModID(modid))
 	}
 }
 
 pub async fn modname_to_modid(modname: &str, api_key: &str) -> Result<u64, APIError> {
-	let url = format!(r#"/IPublishedFileService/QueryFiles/v1/?key={api_key}&appid={APP_ID}&input_json={{"required_kv_tags":[{{"key":"name","value":"{modname}"}}]}}"#);
-	let res = get_steam::<ModIDListResponse>(&url).await?;
+	validate_modname(modname)?;
+
+	let input = QueryFilesInput {
+		required_kv_tags: vec![KVTagFilter { key: "name", value: modname }],
+	};
+	let query = QueryFilesQuery {
+		input_json: Some(serde_json::to_string(&input).map_err(serialize_error)?),
+		..QueryFilesQuery::new(api_key)
+	};
+	let res = get_steam::<ModIDListResponse>("/IPublishedFileService/QueryFiles/v1/", &query).await?;
 	
-	match res.publishedfiledetails {
-		Some(pfd) => Ok(pfd[0].publishedfileid.parse().unwrap()),
-		None => Err(APIError::InvalidModName(modname.to_owned()))
-	}
+	res.publishedfiledetails
+		.and_then(|pfd| pfd.first().and_then(|f| f.publishedfileid.parse().ok()))
+		.ok_or_else(|| APIError::ModNameNotFound(modname.to_owned()))
 }
 
 // Idea: filter by tag: &requiredtags[0]=Both&requiredtags[1]=Client&requiredtags[2]=Server&requiredtags[3]=NoSync&match_all_tags=false
 pub async fn get_mod_list(client: &reqwest::Client, cursor: &str, api_key: &str) -> Result<ModListResponse, APIError> {
-	let c = urlencoding::encode(cursor);
-	let url = format!("{STEAM_API_URL}/IPublishedFileService/QueryFiles/v1/?key={api_key}&appid={APP_ID}&cursor={c}\
-		&numperpage=10000\
-		&cache_max_age_seconds=0\
-		&return_details=true\
-		&return_kv_tags=true\
-		&return_children=true\
-		&return_tags=true\
-		&return_vote_data=true"
-	);
-
-	log::debug!("Requesting SteamAPI at: {url}");
-	let res = client.get(url).send().await?;
-    let mod_list = res.json::<Response<ModListResponse>>().await?.response;
+	let query = QueryFilesQuery {
+		cursor: Some(cursor),
+		numperpage: Some(10000),
+		cache_max_age_seconds: Some(0),
+		return_details: Some(true),
+		return_kv_tags: Some(true),
+		return_children: Some(true),
+		return_tags: Some(true),
+		return_vote_data: Some(true),
+		..QueryFilesQuery::new(api_key)
+	};
+	let mod_list = get_steam_with::<ModListResponse>(client, "/IPublishedFileService/QueryFiles/v1/", &query).await?;
 
 	if let Some(files) = mod_list.publishedfiledetails.as_ref() {
     	check_missing_fields(files);
@@ -280,18 +363,17 @@ pub async fn get_mod_list(client: &reqwest::Client, cursor: &str, api_key: &str)
 }
 
 pub async fn steamname_to_steamid(steamname: &str, api_key: &str) -> Result<u64, APIError> {
-	let url = format!("/ISteamUser/ResolveVanityURL/v1/?key={}&vanityurl={}", api_key, steamname);
-	let res: IDResponse = get_steam(&url).await?;
+	let query = ResolveVanityURLQuery { key: api_key, vanityurl: steamname };
+	let res: IDResponse = get_steam("/ISteamUser/ResolveVanityURL/v1/", &query).await?;
 	
-	match res.steamid {
-		Some(id) => Ok(id.parse().unwrap()),
-		None => Err(APIError::SteamNameNotResolveable(steamname.to_owned()))
-	}
+	res.steamid
+		.and_then(|id| id.parse().ok())
+		.ok_or_else(|| APIError::SteamNameNotResolveable(steamname.to_owned()))
 }
 
 pub async fn get_user_info(steamid: u64, api_key: &str) -> Result<SteamUserInfo, APIError> {
-	let url = format!("/ISteamUser/GetPlayerSummaries/v2/?key={}&steamids={}", api_key, steamid);
-	let res: SteamUserInfoResponse = get_steam(&url).await?;
+	let query = GetPlayerSummariesQuery { key: api_key, steamids: steamid.to_string() };
+	let res: SteamUserInfoResponse = get_steam("/ISteamUser/GetPlayerSummaries/v2/", &query).await?;
 	
 	match res.players.first() {
 		Some(user) => Ok(user.clone()),
@@ -301,8 +383,8 @@ pub async fn get_user_info(steamid: u64, api_key: &str) -> Result<SteamUserInfo,
 
 pub async fn get_users_info(steamids: &[u64], api_key: &str) -> Result<Vec<SteamUserInfo>, APIError> {
 	let steamids_csv = steamids.iter().map(u64::to_string).collect::<Vec<_>>().join(",");
-	let url = format!("/ISteamUser/GetPlayerSummaries/v2/?key={}&steamids={}", api_key, steamids_csv);
-	let res: SteamUserInfoResponse = get_steam(&url).await?;
+	let query = GetPlayerSummariesQuery { key: api_key, steamids: steamids_csv };
+	let res: SteamUserInfoResponse = get_steam("/ISteamUser/GetPlayerSummaries/v2/", &query).await?;
 	
 	return Ok(res.players);
 }
@@ -313,4 +395,18 @@ pub fn validate_steamid64(steamid: u64) -> Result<u64, APIError> {
 		0x0110000100000001..=0x01100001FFFFFFFF => Ok(steamid),
 		_ => Err(APIError::InvalidSteamID(steamid))
 	}
+}
+
+// custom profile urls are at most 32 letters, digits, underscores or dashes
+pub fn is_vanity_name(steamname: &str) -> bool {
+	(1..=32).contains(&steamname.len()) && steamname.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-'))
+}
+
+// internal mod names are C# identifiers, anything else can't be the name of a mod
+pub fn validate_modname(modname: &str) -> Result<&str, APIError> {
+	let mut chars = modname.chars();
+	match chars.next() {
+		Some(c) if (c.is_ascii_alphabetic() || c == '_') && chars.all(|c| c.is_ascii_alphanumeric() || c == '_') => Ok(modname),
+		_ => Err(APIError::InvalidModName(modname.to_owned()))
+	}
 }
\ No newline at end of file
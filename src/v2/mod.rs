@@ -0,0 +1,56 @@
+pub mod schema;
+
+use rocket::State;
+use rocket::response::content::RawHtml;
+use rocket::serde::json::{serde_json, Value};
+use rocket_cache_response::CacheResponse;
+use crate::{api13, api14, stale_json, APIError};
+use crate::api13::Api13State;
+use crate::api14::Api14State;
+use crate::cache::Stale;
+use schema::Mod;
+
+#[get("/")]
+fn index_v2() -> RawHtml<&'static str> {
+	RawHtml(r#"
+		<h1>v2 Index</h1>
+		<p>1.3 and 1.4 mods in the same format</p>
+		<form action="javascript: window.location.href = '/v2/' + document.getElementById('version').value + '/mod/' + document.getElementById('input').value">
+			<select id="version">
+				<option value="1.4">1.4</option>
+				<option value="1.3">1.3</option>
+			</select>
+			<label for="input">Mod name (or ID on 1.4):</label>
+			<input type="text" id="input">
+			<input type="submit" value="Go" />
+		</form>
+
+		<a href="/">go back</a><br>
+	"#)
+}
+
+#[get("/1.3/mod/<modname>")]
+async fn mod_1_3(modname: &str, state: &State<Api13State>) -> Result<Stale<CacheResponse<Value>>, APIError> {
+	let (info, stale_for) = api13::api::get_mod_info(modname, state).await?;
+
+	let info = Mod::from(info);
+	stale_json!(info, stale_for, 3600, false)
+}
+
+#[get("/1.4/mod/<modid>", rank=1)]
+async fn mod_1_4(modid: u64, state: &State<Api14State>) -> Result<Stale<CacheResponse<Value>>, APIError> {
+	let (mod_data, stale_for) = api14::mod_api::get_mod_data(modid, state).await?;
+
+	let info = Mod::from(api14::mod_api::get_filtered_mod_info(&mod_data));
+	stale_json!(info, stale_for, 3600, false)
+}
+
+#[get("/1.4/mod/<modname>", rank=2)]
+async fn mod_1_4_str(modname: &str, state: &State<Api14State>) -> Result<Stale<CacheResponse<Value>>, APIError> {
+	let mod_id = state.name_cache.mod_id(modname, &state.steam_api_key).await?;
+	mod_1_4(mod_id, state).await
+}
+
+pub fn get_routes() -> Vec<rocket::Route> {
+	routes![index_v2, mod_1_3, mod_1_4, mod_1_4_str]
+}
@@ -0,0 +1,132 @@
+// the response schema of /v2, the same for 1.3 and 1.4 mods. Fields only one of them has are null for the other
+use chrono::{DateTime, Utc};
+use rocket::serde::Serialize;
+use schemars::JsonSchema;
+use crate::{api13, api14, steamapi};
+use crate::mod_types::{ModReference, ModSide};
+
+#[derive(Serialize, Clone, Copy, PartialEq, Debug, JsonSchema)]
+#[serde(crate = "rocket::serde")]
+pub enum GameVersion {
+	#[serde(rename = "1.3")]
+	Legacy,
+	#[serde(rename = "1.4")]
+	Current
+}
+
+#[derive(Serialize, Clone, Debug, JsonSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct ModVersion {
+	pub version: String,
+	pub tmodloader_version: String,
+}
+
+// statistics of the steam workshop, only 1.4 mods have them
+#[derive(Serialize, Clone, Debug, JsonSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct WorkshopStats {
+	pub favorited: u32,
+	pub followers: u32,
+	pub views: u64,
+	pub num_comments: u32,
+	pub playtime: String,
+	pub vote_data: Option<steamapi::VoteData>,
+	pub tags: Vec<steamapi::ModTag>,
+	pub children: Vec<String>,
+}
+
+#[derive(Serialize, Clone, Debug, JsonSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct Mod {
+	pub game_version: GameVersion,
+	pub internal_name: String,
+	pub display_name: String,
+	// workshop id, 1.4 only
+	pub mod_id: Option<String>,
+	pub author: String,
+	// steam id of the uploader, 1.4 only
+	pub author_id: Option<String>,
+	pub modside: ModSide,
+	pub versions: Vec<ModVersion>,
+	pub mod_references: Vec<ModReference>,
+	pub homepage: Option<String>,
+	pub description: Option<String>,
+	pub icon_url: Option<String>,
+	// 1.3 only, 1.4 mods are downloaded through the workshop
+	pub download_url: Option<String>,
+	pub downloads_total: u64,
+	// 1.3 only
+	pub downloads_yesterday: Option<u64>,
+	pub time_updated: Option<DateTime<Utc>>,
+	// 1.4 only
+	pub time_created: Option<DateTime<Utc>>,
+	pub workshop: Option<WorkshopStats>,
+}
+
+fn non_empty(value: String) -> Option<String> {
+	(!value.is_empty()).then_some(value)
+}
+
+// steam uses 0 for missing timestamps
+fn from_unix(secs: u64) -> Option<DateTime<Utc>> {
+	(secs > 0).then(|| DateTime::from_timestamp(secs as i64, 0)).flatten()
+}
+
+impl From<api13::responses::ModInfo> for Mod {
+	fn from(info: api13::responses::ModInfo) -> Self {
+		Mod {
+			game_version: GameVersion::Legacy,
+			time_updated: api13::parse::parse_timestamp(&info.updateTimeStamp).ok(),
+			internal_name: info.name,
+			display_name: info.displayname,
+			mod_id: None,
+			author: info.author,
+			author_id: None,
+			modside: info.modside,
+			versions: vec![ModVersion { version: info.version, tmodloader_version: info.modloaderversion }],
+			mod_references: info.modreferences,
+			homepage: info.homepage.and_then(non_empty),
+			description: info.description.and_then(non_empty),
+			icon_url: info.icon,
+			download_url: non_empty(info.download),
+			downloads_total: info.downloads as u64,
+			downloads_yesterday: Some(info.hot as u64),
+			time_created: None,
+			workshop: None,
+		}
+	}
+}
+
+impl From<api14::responses::ModInfo> for Mod {
+	fn from(info: api14::responses::ModInfo) -> Self {
+		Mod {
+			game_version: GameVersion::Current,
+			internal_name: info.internal_name,
+			display_name: info.display_name,
+			mod_id: Some(info.mod_id.to_string()),
+			author: info.author,
+			author_id: non_empty(info.author_id),
+			modside: info.modside.parse().unwrap_or_default(),
+			versions: info.versions.into_iter().map(|v| ModVersion { version: v.mod_version, tmodloader_version: v.tmodloader_version }).collect(),
+			mod_references: api13::parse::parse_mod_references(&info.mod_references),
+			homepage: non_empty(info.homepage),
+			description: info.description.and_then(non_empty),
+			icon_url: non_empty(info.workshop_icon_url),
+			download_url: None,
+			downloads_total: info.downloads_total as u64,
+			downloads_yesterday: None,
+			time_created: from_unix(info.time_created),
+			time_updated: from_unix(info.time_updated),
+			workshop: Some(WorkshopStats {
+				favorited: info.favorited,
+				followers: info.followers,
+				views: info.views,
+				num_comments: info.num_comments,
+				playtime: info.playtime,
+				vote_data: info.vote_data,
+				tags: info.tags.unwrap_or_default(),
+				children: info.children.unwrap_or_default().iter().map(u64::to_string).collect(),
+			}),
+		}
+	}
+}
@@ -0,0 +1,35 @@
+#!/bin/sh
+# saves the pages of the 1.3 server into captured/, where the parser tests pick them up. Every page is listed in
+# captured/sources.txt with the url and date it was captured on, html pages also note it in their first line.
+#   tests/fixtures/api13/capture.sh <steamid64 of a mod author> <internal name of one of their mods>
+# The hand-written pages next to this script stay, the tests check exact values against them
+set -eu
+
+API_URL="http://javid.ddns.net/tModLoader"
+DIR="$(dirname "$0")/captured"
+STEAMID="$1"
+MODNAME="$2"
+
+mkdir -p "$DIR"
+: > "$DIR/sources.txt"
+
+capture() {
+	url="$API_URL/$1"
+	file="$DIR/$2"
+	date="$(date -u +%Y-%m-%d)"
+	page="$(curl -sS --max-time 30 "$url")"
+	case "$file" in
+		*.html) printf '<!-- captured from %s on %s -->\n%s\n' "$url" "$date" "$page" > "$file" ;;
+		*) printf '%s\n' "$page" > "$file" ;;
+	esac
+	printf '%s %s %s\n' "$2" "$url" "$date" >> "$DIR/sources.txt"
+	echo "saved $file"
+}
+
+capture "modmigrationprogress.php" "modmigrationprogress.html"
+capture "modmigrationprogressalltime.php" "modmigrationprogressalltime.html"
+capture "tools/ranksbysteamid.php?steamid64=$STEAMID" "ranksbysteamid.html"
+capture "tools/moddownloadhistory.php?modname=$MODNAME" "moddownloadhistory.html"
+capture "tools/modinfo.php?modname=$MODNAME" "modinfo.json"
+# what the server answers for names it doesn't know, parse_mod_info has to return None for it
+capture "tools/modinfo.php?modname=ThisModDoesNotExist1234" "modinfo-unknown.json"